@@ -1,89 +1,468 @@
-use anyhow::Result;
-use clap::{value_parser, Arg, ArgAction, ArgGroup, ArgMatches, Command};
-use rspotify::model::SearchType;
+use std::{
+    collections::HashSet,
+    io::{IsTerminal, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-use crate::{auth, ui};
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use clap::{error::ErrorKind, value_parser, Arg, ArgAction, ArgGroup, ArgMatches, Command};
+use ratatui::crossterm::terminal;
+use rspotify::{
+    model::{Device, Market, PlayableId, RepeatState, SearchType, TimeRange},
+    prelude::Id,
+};
 
-/// Describes a volume operation either increase, decrease or set.
+#[cfg(all(target_os = "linux", feature = "mpris"))]
+use crate::mpris;
+use crate::{
+    art, auth,
+    client::{PlaybackSnapshot, SearchFilter, SpotifyPlayer},
+    color,
+    config::{self, Config},
+    error::{self, Error},
+    format, fuzzy, lyrics,
+    model::{Playable, PlayableSummary, Status, StatusEvent, StatusSummary},
+    output, repl, tui, ui,
+};
+
+/// The format used by the bar command, if none is specified
+const DEFAULT_BAR_FORMAT: &str = "{icon} {title} - {artist}";
+
+/// The format used by the current command when writing to a file, if none is specified
+const DEFAULT_CURRENT_FORMAT: &str = "\"{title}\" by {artist}";
+
+/// The format of the line continuously printed by the current command when following
+const FOLLOW_FORMAT: &str = "{icon} {title} - {artist} {bar} {progress} / {duration}";
+
+/// The format of the current command showing the remaining time instead
+const REMAINING_CURRENT_FORMAT: &str = "\"{title}\" by {artist} ({remaining})";
+
+/// The format of the line printed when following, showing the remaining time instead
+const REMAINING_FOLLOW_FORMAT: &str = "{icon} {title} - {artist} {bar} {remaining}";
+
+/// Libraries with more playlists than this are too large to comfortably select from
+const LARGE_LIBRARY_SIZE: usize = 100;
+
+/// The amount of items searched per type when searching for all items, so a search stays fast
+const SEARCH_ALL_LIMIT: u32 = 200;
+
+/// The host of links to Spotify items, e.g. "https://open.spotify.com/track/<id>"
+const SPOTIFY_LINK_HOST: &str = "open.spotify.com";
+
+/// Describes a volume operation either increase, decrease, set or get.
+///
+/// Increases and decreases are either in percentage points or in percent of the current volume.
+/// A bare + or - steps by the configured volume step
 #[derive(Clone)]
 enum VolumeOperation {
     Increase(u8),
     Decrease(u8),
+    StepUp,
+    StepDown,
+    IncreasePercent(u8),
+    DecreasePercent(u8),
     Set(u8),
+    Get,
 }
 
-/// Describes a shuffle operation either on or off.
+/// Describes a shuffle operation either on, off or status.
 #[derive(Clone)]
 enum ShuffleOperation {
     On,
     Off,
+    Status,
 }
 
-/// Describes a repeat operation either on, off or track.
+/// Describes a repeat operation either on, off, track or status.
 #[derive(Clone)]
 enum RepeatOperation {
     On,
     Off,
     Track,
+    Status,
 }
 
-/// Parse the command line arguments
-pub async fn parse() -> Result<()> {
-    let matches = command().get_matches();
+/// Run the parsed command line arguments
+pub async fn parse(matches: ArgMatches) -> Result<()> {
+    color::init(matches.get_flag("no-color"));
+
+    // The status bar output never prompts and always succeeds, so it is handled before anything
+    // which could fail or ask the user for input
+    if let Some(bar) = matches.subcommand_matches("bar") {
+        print_bar(bar, bar_status(&matches).await.ok().flatten());
+        return Ok(());
+    }
+
+    // Skips everything not needed for printing the current track, so it is as fast as possible
+    if matches.subcommand_matches("now").is_some() {
+        let mut player = unprompted_player(&matches).await?.ok_or(Error::Auth)?;
+        return print_now(&mut player).await;
+    }
+
+    let config = config::load()?;
+
+    // The proxy is applied before the runtime starts, where an invalid one is ignored
+    config.proxy()?;
+
+    let flow = auth::FlowOptions {
+        callback_port: matches.get_one::<u16>("callback-port").copied(),
+        // A browser opened during a remote session would open on the remote machine
+        open_browser: !matches.get_flag("no-open") && !auth::remote_session(),
+        callback_mode: match matches.get_flag("manual-auth") {
+            true => auth::CallbackMode::Manual,
+            false => auth::CallbackMode::Server,
+        },
+        callback_timeout: matches
+            .get_one::<Duration>("callback-timeout")
+            .copied()
+            .unwrap_or(auth::DEFAULT_CALLBACK_TIMEOUT),
+    };
 
     if matches.get_flag("authorize") {
-        auth::run_flow().await?;
+        if let Some((name, _)) = matches.subcommand() {
+            command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    format!(
+                        "the subcommand '{}' cannot be used with '--authorize'",
+                        name
+                    ),
+                )
+                .exit();
+        }
+
+        auth::run_flow(&flow).await.context(Error::Auth)?;
         return Ok(());
     }
 
     // Get SpotifyPlayer instance, run auth flow if user is unauthorized
-    let mut player = match auth::load_cached().await? {
+    let cached = match matches.get_flag("force-reauth") {
+        true => {
+            auth::clear_tokens()?;
+            None
+        }
+        false => auth::load_cached(std::io::stdin().is_terminal())
+            .await
+            .context(Error::Auth)?,
+    };
+
+    let mut player = match cached {
         Some(player) => player,
-        None => auth::run_flow().await?,
+        None => auth::run_flow(&flow).await.context(Error::Auth)?,
     };
 
-    if let Some(_) = matches.subcommand_matches("current") {
+    player.set_max_retries(config.max_retries());
+    player.set_default_device(config.default_device().map(|d| d.to_string()));
+    player.set_dry_run(matches.get_flag("dry-run"));
+    player.set_clean_fallback(config.clean_fallback());
+    player.set_smart_prev(config.smart_prev());
+    player.set_toggle_fallback(config.toggle_fallback());
+
+    if let Some(timeout) = timeout_matches(&matches, &config)? {
+        player.set_request_timeout(timeout);
+    }
+
+    match matches.get_flag("no-cache") {
+        true => player.set_search_cache_ttl(Duration::ZERO),
+        false => player.set_search_cache_ttl(config.search_cache_ttl()),
+    }
+
+    switch_device(&mut player, &matches).await?;
+
+    #[cfg(all(target_os = "linux", feature = "mpris"))]
+    if matches.subcommand_matches("daemon").is_some() {
+        return mpris::run(player, poll_interval(&matches, &config)).await;
+    }
+
+    if matches.subcommand_matches("repl").is_some() {
+        return repl::run(&mut player, &config).await;
+    }
+
+    if let Some(batch) = matches.subcommand_matches("batch") {
+        return repl::batch(&mut player, &config, batch.get_flag("continue-on-error")).await;
+    }
+
+    dispatch(&mut player, &matches, &config).await
+}
+
+/// Run the subcommand of the argument matches
+pub async fn dispatch(
+    player: &mut SpotifyPlayer,
+    matches: &ArgMatches,
+    config: &Config,
+) -> Result<()> {
+    if let Some(bar) = matches.subcommand_matches("bar") {
+        print_bar(bar, player.status().await.ok().flatten());
+        return Ok(());
+    }
+
+    if matches.subcommand_matches("now").is_some() {
+        return print_now(player).await;
+    }
+
+    if let Some(current) = matches.subcommand_matches("current") {
+        let output = current.get_one::<PathBuf>("output");
+
+        if current.get_flag("follow") {
+            // A JSON line can't be cut to the terminal width, so it is only written to files
+            if current.get_flag("json") && output.is_none() {
+                return Err(anyhow!(
+                    "--json can only be used with --follow when writing to a file using --output, use --json-lines to print every change instead"
+                ));
+            }
+
+            return follow_current(player, current, poll_interval(matches, config)).await;
+        }
+
+        // Everything spotify returns is printed, including fields we don't use
+        if current.get_flag("raw") {
+            let playback = player.raw_playback().await?;
+
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&playback)
+                    .context("Failed serializing the playback state")?
+            );
+
+            return Ok(());
+        }
+
+        // Only the ID is printed, so scripts can use the output as is. Nothing is printed if
+        // nothing is playing
+        if current.get_flag("id") {
+            if let Some(id) = player.current_id().await? {
+                println!("{}", id);
+            }
+
+            return Ok(());
+        }
+
+        if current.get_one::<String>("format").is_some()
+            || current.get_flag("json")
+            || output.is_some()
+        {
+            let line = current_line(current, player.status().await?.as_ref(), false)?;
+
+            return match output {
+                Some(path) => output::write_atomic(path, &format!("{}\n", line)),
+                None => {
+                    println!("{}", line);
+                    Ok(())
+                }
+            };
+        }
+
         let track = player.current_track().await?;
 
+        if let (Some(t), true) = (&track, current.get_flag("art")) {
+            art::print_cover(
+                &t.cover_images,
+                current.get_one::<art::Backend>("art-backend").copied(),
+            )
+            .await?;
+        }
+
         match track {
-            Some(t) => println!("\"{}\" by {}", t.title, t.by.join(", ")),
+            Some(t) => println!(
+                "\"{}\" by {}",
+                color::name(&t.title),
+                color::artists(&t.by.join(", "))
+            ),
+            None => println!("Nothing playing"),
+        }
+
+        return Ok(());
+    }
+
+    if let Some(lyrics) = matches.subcommand_matches("lyrics") {
+        match player.status().await? {
+            Some(status) if matches!(status.track.id, Some(PlayableId::Episode(_))) => {
+                println!("Lyrics are only available for tracks")
+            }
+            Some(status) => {
+                lyrics::print_lyrics(config.lyrics_provider(), &status, lyrics.get_flag("synced"))
+                    .await?
+            }
             None => println!("Nothing playing"),
         }
 
         return Ok(());
     }
 
-    if let Some(_) = matches.subcommand_matches("pause") {
+    if let Some(open) = matches.subcommand_matches("open") {
+        let url = match open.get_one::<String>("uri") {
+            Some(url) => url.clone(),
+            None => match player.current_uri().await? {
+                Some(url) => url,
+                None => {
+                    println!("Nothing playing");
+                    return Ok(());
+                }
+            },
+        };
+
+        println!("Opening {}", url);
+
+        // Spotify links open in the desktop app, if it is installed
+        open::that(&url).context("Failed opening the link")?;
+
+        return Ok(());
+    }
+
+    if let Some(wait) = matches.subcommand_matches("wait-track-change") {
+        let change = player.wait_for_track_change(poll_interval(matches, config));
+
+        let status = match wait.get_one::<Duration>("wait-timeout") {
+            Some(timeout) => tokio::time::timeout(*timeout, change)
+                .await
+                .map_err(|_| anyhow!("Timed out waiting for the track to change"))??,
+            None => change.await?,
+        };
+
+        match status {
+            Some(status) => println!(
+                "\"{}\" by {}",
+                color::name(&status.track.title),
+                color::artists(&status.track.by.join(", "))
+            ),
+            None => println!("Playback stopped"),
+        }
+
+        return Ok(());
+    }
+
+    if matches.subcommand_matches("pause").is_some() {
         return player.playback_pause().await;
     }
 
-    if let Some(_) = matches.subcommand_matches("resume") {
+    if matches.subcommand_matches("stop").is_some() {
+        if !player.stop().await? {
+            println!("Nothing playing");
+        }
+
+        return Ok(());
+    }
+
+    if matches.subcommand_matches("resume").is_some() {
         return player.playback_resume().await;
     }
 
-    if let Some(_) = matches.subcommand_matches("toggle") {
+    if matches.subcommand_matches("toggle").is_some() {
         return player.playback_toggle().await;
     }
 
     if let Some(vol) = matches.subcommand_matches("volume") {
-        if let Some(op) = vol.get_one::<VolumeOperation>("amount") {
-            return match op.clone() {
-                VolumeOperation::Increase(i) => player.volume_up(i).await,
-                VolumeOperation::Decrease(d) => player.volume_down(d).await,
-                VolumeOperation::Set(s) => player.volume_set(s).await,
-            };
-        }
+        return match vol.get_one::<VolumeOperation>("amount").cloned() {
+            Some(VolumeOperation::Increase(i)) => player.volume_up(i).await,
+            Some(VolumeOperation::Decrease(d)) => player.volume_down(d).await,
+            Some(VolumeOperation::StepUp) => player.volume_up(config.volume_step()).await,
+            Some(VolumeOperation::StepDown) => player.volume_down(config.volume_step()).await,
+            Some(VolumeOperation::IncreasePercent(i)) => player.volume_scale(100 + i as u32).await,
+            Some(VolumeOperation::DecreasePercent(d)) => player.volume_scale(100 - d as u32).await,
+            Some(VolumeOperation::Set(s)) => player.volume_set(s).await,
+            Some(VolumeOperation::Get) | None => {
+                let volume = player.volume_get().await?;
+
+                match vol.get_flag("json") {
+                    true => println!("{}", serde_json::json!({ "volume": volume })),
+                    false => println!("{}%", volume),
+                }
+
+                Ok(())
+            }
+        };
     }
 
     if let Some(play) = matches.subcommand_matches("play") {
-        if let Some(play_type) = type_matches(play) {
-            if let Some(query) = play.get_one::<String>("content") {
-                let res = player.search(query.clone(), play_type, Some(1)).await?;
-
-                if let Some(first) = res.get(0) {
-                    player.play(first).await?;
-                } else {
-                    println!("No matches found")
+        if play.get_flag("clipboard") {
+            let uri = clipboard_uri()?;
+            return player.play_uri(&uri).await;
+        }
+
+        if let Some(query) = play.get_one::<String>("content") {
+            let all = play.get_flag("all");
+
+            // Only the first result is played, unless the remaining ones are queued
+            let limit = match all {
+                true => *play.get_one::<u32>("count").unwrap_or(&10),
+                false => 1,
+            };
+
+            let market = market_matches(play, config)?;
+
+            let res = player
+                .search(
+                    query.clone(),
+                    type_matches(play),
+                    market,
+                    Some(limit),
+                    None,
+                    SearchFilter {
+                        clean: play.get_flag("clean"),
+                        ..Default::default()
+                    },
+                )
+                .await?;
+
+            // Spotify can only add to the end of the queue, which comes right after the current
+            // track unless other items were queued before
+            if play.get_flag("next") {
+                match res.first() {
+                    Some(first) => {
+                        player.add_to_queue(first.as_ref()).await?;
+                        println!(
+                            "Queued {}, it plays after anything queued before",
+                            first.to_display()
+                        );
+                    }
+                    None if play.get_flag("clean") => println!("No clean matches found"),
+                    None => println!("No matches found"),
+                }
+
+                return Ok(());
+            }
+
+            let before = wait_snapshot(player, play).await;
+
+            match res.first() {
+                Some(first) if play.get_flag("top") => {
+                    let id = first
+                        .artist_id()
+                        .context("Only the top tracks of artists can be played")?;
+
+                    player.play_artist_top(id, market).await?
+                }
+                Some(first) if play.get_flag("in-context") => {
+                    player.play_in_album(first.as_ref()).await?
+                }
+                Some(first) if play.get_flag("shuffle") => {
+                    player.play_shuffled(first.as_ref()).await?
+                }
+                Some(first) => player.play(first.as_ref(), None).await?,
+                None if play.get_flag("clean") => println!("No clean matches found"),
+                None => println!("No matches found"),
+            }
+
+            if let (Some(first), Some(before)) = (res.first(), &before) {
+                player.wait_for_playing(first.as_ref(), before).await?;
+            }
+
+            if all {
+                let queueable: Vec<&dyn Playable> = res
+                    .iter()
+                    .skip(1)
+                    .map(|r| r.as_ref())
+                    .filter(|r| r.playable_id().is_some())
+                    .collect();
+
+                for item in &queueable {
+                    player.add_to_queue(*item).await?;
+                }
+
+                if !queueable.is_empty() {
+                    println!("Queued {} more items", queueable.len());
                 }
             }
         }
@@ -92,103 +471,718 @@ pub async fn parse() -> Result<()> {
     }
 
     if let Some(search) = matches.subcommand_matches("search") {
-        if let Some(search_type) = type_matches(search) {
-            if let Some(query) = search.get_one::<String>("content") {
-                let count = search.get_one::<u32>("count").unwrap_or_else(|| &10);
+        if let Some(query) = search.get_one::<String>("content") {
+            let count = match search.get_one::<u32>("count") {
+                _ if search.get_flag("all") => &SEARCH_ALL_LIMIT,
+                Some(0) => &SEARCH_ALL_LIMIT,
+                Some(count) => count,
+                None => &10,
+            };
+            let search_types = type_matches(search);
+            let market = market_matches(search, config)?;
+            let clean = search.get_flag("clean");
+            let filter = SearchFilter {
+                clean,
+                dedup: search.get_flag("dedup"),
+            };
+
+            let mut res = player
+                .search(
+                    query.clone(),
+                    search_types.clone(),
+                    market,
+                    Some(*count),
+                    None,
+                    filter,
+                )
+                .await?;
 
-                let res = player
-                    .search(query.clone(), search_type, Some(*count))
-                    .await?;
+            if search.get_flag("json") {
+                return print_summaries(&res);
+            }
+
+            if search.get_flag("markdown") {
+                print_markdown(&res);
+                return Ok(());
+            }
+
+            if res.is_empty() && clean {
+                println!("No clean matches found");
+                return Ok(());
+            }
+
+            let detailed = search.get_flag("detailed");
+
+            if detailed {
+                res = player.with_details(res).await?;
+            }
+
+            if search.get_flag("multi") {
+                if res.is_empty() {
+                    println!("No matches found");
+                    return Ok(());
+                }
+
+                return queue_selected(player, ui::multiselect_playable(res)?, search).await;
+            }
+
+            let selected = match search.get_one::<u32>("select") {
+                Some(n) => {
+                    let found = res.len();
+
+                    res.into_iter().nth(*n as usize - 1).ok_or_else(|| {
+                        anyhow!(
+                            "Can't select result {}, the search only returned {} results",
+                            n,
+                            found
+                        )
+                    })?
+                }
+                None => {
+                    // Keep fetching the next page as long as the user requests more items
+                    let mut offset = 0;
+                    let mut load_more = !res.is_empty();
+                    let mut cursor = 0;
+
+                    loop {
+                        match ui::select_playable_paged(&res, load_more, cursor)? {
+                            ui::PagedSelection::Selected(i) => {
+                                break drill_in(player, res.swap_remove(i)).await?
+                            }
+                            ui::PagedSelection::LoadMore => {
+                                offset += count;
+
+                                let more = player
+                                    .search(
+                                        query.clone(),
+                                        search_types.clone(),
+                                        market,
+                                        Some(*count),
+                                        Some(offset),
+                                        filter,
+                                    )
+                                    .await?;
+
+                                load_more = !more.is_empty();
+
+                                // Start at the first loaded item, or at the last item if nothing
+                                // more was found, since the load more option is gone then
+                                cursor = match load_more {
+                                    true => res.len(),
+                                    false => res.len() - 1,
+                                };
+
+                                match detailed {
+                                    true => res.extend(player.with_details(more).await?),
+                                    false => res.extend(more),
+                                }
+                            }
+                        }
+                    }
+                }
+            };
+
+            // Only tracks and episodes can be queued, so everything else is played right away
+            let action = match (search.get_flag("queue"), search.get_flag("play")) {
+                (true, _) => ui::ItemAction::Queue,
+                (_, true) => ui::ItemAction::Play,
+                _ if search.contains_id("select") || selected.playable_id().is_none() => {
+                    ui::ItemAction::Play
+                }
+                _ => ui::select_item_action()?,
+            };
+
+            return match action {
+                ui::ItemAction::Play => player.play(selected.as_ref(), None).await,
+                ui::ItemAction::Queue => {
+                    player.add_to_queue(selected.as_ref()).await?;
+                    println!("Queued {}", selected.to_display());
+                    Ok(())
+                }
+                ui::ItemAction::Cancel => Ok(()),
+            };
+        }
+    }
+
+    if let Some(follow) = matches.subcommand_matches("follow") {
+        if let Some(selected) = search_single(player, follow, config).await? {
+            return player.follow(selected.as_ref()).await;
+        }
 
-                let selected = ui::select_playable(res)?;
+        return Ok(());
+    }
+
+    if let Some(unfollow) = matches.subcommand_matches("unfollow") {
+        if let Some(selected) = search_single(player, unfollow, config).await? {
+            if !confirmed(unfollow, &format!("Unfollow {}?", selected.to_display()))? {
+                println!("Cancelled");
+                return Ok(());
+            }
+
+            return player.unfollow(selected.as_ref()).await;
+        }
+
+        return Ok(());
+    }
+
+    if let Some(create) = matches.subcommand_matches("create-playlist") {
+        if let Some(name) = create.get_one::<String>("name") {
+            let playlist = player
+                .create_playlist(
+                    name,
+                    create.get_flag("public"),
+                    create.get_one::<String>("description").map(|d| d.as_str()),
+                )
+                .await?;
+
+            // Nothing is created during a dry run
+            if let Some(playlist) = playlist {
+                match playlist.external_urls.get("spotify") {
+                    Some(url) => println!("Created playlist \"{}\": {}", playlist.name, url),
+                    None => println!("Created playlist \"{}\"", playlist.name),
+                }
+            }
+        }
+
+        return Ok(());
+    }
 
-                return player.play(&selected).await;
+    if let Some(queue) = matches.subcommand_matches("queue") {
+        if let Some(name) = queue.get_one::<String>("save") {
+            // Nothing is saved during a dry run
+            if let Some((playlist, saved)) = player.save_queue(name).await? {
+                match playlist.external_urls.get("spotify") {
+                    Some(url) => println!(
+                        "Saved {} items to playlist \"{}\": {}",
+                        saved, playlist.name, url
+                    ),
+                    None => println!("Saved {} items to playlist \"{}\"", saved, playlist.name),
+                }
             }
+
+            return Ok(());
+        }
+
+        let (current, upcoming) = player.queue().await?;
+
+        if current.is_none() && upcoming.is_empty() {
+            println!("The queue is empty");
+            return Ok(());
+        }
+
+        if let Some(t) = current {
+            println!(
+                "Now playing \"{}\" by {}",
+                color::name(&t.title),
+                color::artists(&t.by.join(", "))
+            );
+        }
+
+        for (i, t) in upcoming.iter().enumerate() {
+            println!(
+                "{}. \"{}\" by {}",
+                i + 1,
+                color::name(&t.title),
+                color::artists(&t.by.join(", "))
+            );
         }
+
+        return Ok(());
     }
 
     if let Some(library) = matches.subcommand_matches("library") {
-        let playlists = player.playlists().await?;
+        let mut playlists = player
+            .playlists(
+                library.get_one::<u32>("limit").copied(),
+                library.get_one::<u32>("offset").copied(),
+            )
+            .await?;
 
-        let selected_playlist = match library.get_one::<String>("name") {
-            Some(filter) => playlists.into_iter().find(|p| {
+        if library.get_flag("mine") {
+            let user_id = player.user_id().await?;
+
+            playlists.retain(|p| p.owner().is_some_and(|o| o.id == user_id));
+        }
+
+        if let Some(owner) = library.get_one::<String>("owner") {
+            playlists.retain(|p| {
+                p.owner()
+                    .and_then(|o| o.display_name.as_ref())
+                    .is_some_and(|n| n.to_lowercase().contains(&owner.to_lowercase()))
+            });
+        }
+
+        if let Some(filter) = library.get_one::<String>("filter") {
+            playlists.retain(|p| {
                 p.to_display()
                     .to_lowercase()
                     .contains(&filter.to_lowercase())
-            }),
+            });
+        }
+
+        if library.get_flag("json") {
+            return print_summaries(&playlists);
+        }
+
+        if library.get_flag("markdown") {
+            print_markdown(&playlists);
+            return Ok(());
+        }
+
+        // Scrolling through a huge selection is tedious, so point out how to narrow it down
+        if library.get_one::<String>("name").is_none() && playlists.len() > LARGE_LIBRARY_SIZE {
+            eprintln!(
+                "Fetched {} playlists, pass a name, use --filter or --limit to narrow them down",
+                playlists.len()
+            );
+        }
+
+        let selected_playlist = match library.get_one::<String>("name") {
+            Some(filter) => pick_match(
+                fuzzy::find(playlists, filter, |p| p.to_display()),
+                ui::select_playable,
+            )?,
+            None if playlists.is_empty() => None,
+            None if library.get_flag("detailed") => {
+                Some(ui::select_playable(player.with_details(playlists).await?)?)
+            }
             None => Some(ui::select_playable(playlists)?),
         };
 
         match selected_playlist {
-            Some(p) => player.play(&p).await?,
+            Some(p) => {
+                player
+                    .play(
+                        p.as_ref(),
+                        library.get_one::<u32>("start").map(|start| start - 1),
+                    )
+                    .await?
+            }
             None => println!("No matching library playlist found"),
         }
 
         return Ok(());
     }
 
-    if let Some(device) = matches.subcommand_matches("device") {
-        let devices = player.devices().await?;
+    if let Some(whoami) = matches.subcommand_matches("whoami") {
+        let user = player.me().await?;
 
-        let selected_device = match device.get_one::<String>("name") {
-            Some(filter) => devices
-                .into_iter()
-                .find(|d| d.name.to_lowercase().contains(&filter.to_lowercase())),
-            None => Some(ui::select_device(devices)?),
-        };
+        if whoami.get_flag("json") {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&user).context("Failed serializing user")?
+            );
+            return Ok(());
+        }
 
-        match selected_device {
-            Some(d) => player.set_device(d).await?,
-            None => println!("No matching playback device found"),
+        println!(
+            "Logged in as \"{}\" ({})",
+            color::name(user.display_name.as_deref().unwrap_or(&user.id)),
+            user.id
+        );
+        println!(
+            "Account: {}",
+            color::label(user.product.as_deref().unwrap_or("unknown"))
+        );
+        println!(
+            "Country: {}",
+            color::label(user.country.as_deref().unwrap_or("unknown"))
+        );
+
+        if user.product.as_deref() == Some("free") {
+            println!("Controlling playback requires a Spotify Premium account");
         }
 
         return Ok(());
     }
 
-    if let Some(_) = matches.subcommand_matches("next") {
-        return player.track_next().await;
-    }
+    if let Some(recent) = matches.subcommand_matches("recent") {
+        let since = recent.get_one::<DateTime<Utc>>("since").copied();
+
+        // A time window usually matters more than the amount, so show all tracks played in it
+        let count = recent
+            .get_one::<u32>("count")
+            .copied()
+            .unwrap_or(if since.is_some() { 50 } else { 10 });
+
+        let tracks = player.recently_played(count, since).await?;
+
+        if tracks.is_empty() {
+            println!("No recently played tracks");
+            return Ok(());
+        }
+
+        if recent.get_flag("markdown") {
+            let playables: Vec<Box<dyn Playable>> = tracks
+                .into_iter()
+                .map(|t| Box::new(t) as Box<dyn Playable>)
+                .collect();
+
+            print_markdown(&playables);
+            return Ok(());
+        }
 
-    if let Some(_) = matches.subcommand_matches("prev") {
-        return player.track_prev().await;
+        if recent.get_flag("play") {
+            let playables = tracks
+                .into_iter()
+                .map(|t| Box::new(t) as Box<dyn Playable>)
+                .collect();
+
+            let selected = ui::select_playable(playables)?;
+
+            return player.play(selected.as_ref(), None).await;
+        }
+
+        for (i, t) in tracks.iter().enumerate() {
+            println!(
+                "{}. \"{}\" by {}",
+                i + 1,
+                color::name(&t.title),
+                color::artists(&t.by.join(", "))
+            );
+        }
+
+        return Ok(());
     }
 
-    if let Some(shuffle) = matches.subcommand_matches("shuffle") {
-        return match shuffle.get_one::<ShuffleOperation>("mode") {
-            Some(mode) => match mode {
-                ShuffleOperation::On => player.shuffle_on().await,
-                ShuffleOperation::Off => player.shuffle_off().await,
-            },
-            None => player.shuffle_toggle().await,
+    if let Some(top) = matches.subcommand_matches("top") {
+        let count = top.get_one::<u32>("count").unwrap_or(&10);
+        let time_range = top
+            .get_one::<TimeRange>("time-range")
+            .copied()
+            .unwrap_or(TimeRange::MediumTerm);
+
+        let res = if top.get_flag("artists") {
+            player.top_artists(time_range, *count).await?
+        } else {
+            player.top_tracks(time_range, *count).await?
         };
+
+        if res.is_empty() {
+            println!("No top items found");
+            return Ok(());
+        }
+
+        if top.get_flag("markdown") {
+            print_markdown(&res);
+            return Ok(());
+        }
+
+        let selected = ui::select_playable(res)?;
+
+        return player.play(selected.as_ref(), None).await;
     }
 
-    if let Some(repeat) = matches.subcommand_matches("repeat") {
-        return match repeat.get_one::<RepeatOperation>("mode") {
-            Some(mode) => match mode {
-                RepeatOperation::On => player.repeat_on().await,
-                RepeatOperation::Off => player.repeat_off().await,
-                RepeatOperation::Track => player.repeat_track().await,
-            },
-            None => player.repeat_toggle().await,
-        };
+    if let Some(radio) = matches.subcommand_matches("radio") {
+        let count = radio.get_one::<u32>("count").unwrap_or(&20);
+
+        return player.radio_from_current(*count).await;
+    }
+
+    if let Some(autoplay) = matches.subcommand_matches("autoplay") {
+        let settings = AutoplaySettings {
+            threshold: autoplay
+                .get_one::<u32>("threshold")
+                .copied()
+                .unwrap_or(config.autoplay_threshold()),
+            seeds: autoplay
+                .get_one::<u32>("seeds")
+                .copied()
+                .unwrap_or(config.autoplay_seeds()),
+            count: *autoplay.get_one::<u32>("count").unwrap_or(&10),
+        };
+
+        println!(
+            "Queueing recommendations whenever fewer than {} items are coming up, press Ctrl-C to stop",
+            settings.threshold
+        );
+
+        return tokio::select! {
+            result = autoplay_loop(player, &settings, poll_interval(matches, config)) => result,
+            _ = tokio::signal::ctrl_c() => Ok(()),
+        };
+    }
+
+    if matches.subcommand_matches("tui").is_some() {
+        return tui::run(player, poll_interval(matches, config)).await;
+    }
+
+    if let Some(device) = matches.subcommand_matches("device") {
+        if device.get_flag("disconnect") {
+            if !player.disconnect().await? {
+                println!("No active playback device");
+            }
+
+            return Ok(());
+        }
+
+        let devices = player.devices().await?;
+
+        if device.get_flag("json") {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&devices).context("Failed serializing devices")?
+            );
+            return Ok(());
+        }
+
+        if device.get_flag("list") {
+            if devices.is_empty() {
+                println!("No available playback devices");
+            }
+
+            for d in &devices {
+                println!(
+                    "{} [{}]{}{}",
+                    color::name(&d.name),
+                    color::label(&format!("{:?}", d._type)),
+                    d.volume_percent
+                        .map(|v| format!(" {}%", v))
+                        .unwrap_or_default(),
+                    if d.is_active { " (active)" } else { "" }
+                );
+            }
+
+            return Ok(());
+        }
+
+        let selected_device = match device.get_one::<String>("name") {
+            Some(filter) => find_device(devices, filter)?,
+            None => Some(ui::select_device(devices)?),
+        };
+
+        match selected_device {
+            Some(d) => player.set_device(d, device.get_flag("play")).await?,
+            None => {
+                println!("No matching playback device found");
+                return Ok(());
+            }
+        }
+
+        if device.get_flag("resume") && !player.resume_on_device().await? {
+            println!("Nothing played recently, so there is nothing to resume");
+        }
+
+        return Ok(());
+    }
+
+    if let Some(next) = matches.subcommand_matches("next") {
+        let before = wait_snapshot(player, next).await;
+
+        player.track_next().await?;
+
+        if let Some(before) = before {
+            player.wait_for_item_change(&before).await?;
+        }
+
+        return Ok(());
+    }
+
+    if let Some(prev) = matches.subcommand_matches("prev") {
+        let before = wait_snapshot(player, prev).await;
+
+        // Restarting the current track doesn't change the item, so there is nothing to wait for
+        if player.track_prev(prev.get_flag("smart")).await? {
+            if let Some(before) = before {
+                player.wait_for_item_change(&before).await?;
+            }
+        }
+
+        return Ok(());
+    }
+
+    if matches.subcommand_matches("restart").is_some() {
+        if !player.restart().await? {
+            println!("Nothing playing");
+        }
+
+        return Ok(());
+    }
+
+    if let Some(shuffle) = matches.subcommand_matches("shuffle") {
+        return match shuffle.get_one::<ShuffleOperation>("mode") {
+            Some(mode) => match mode {
+                ShuffleOperation::On => player.shuffle_on().await,
+                ShuffleOperation::Off => player.shuffle_off().await,
+                ShuffleOperation::Status => {
+                    let state = player.shuffle_state().await?;
+
+                    match shuffle.get_flag("json") {
+                        true => println!("{}", serde_json::json!({ "shuffle": state })),
+                        false => println!("{}", if state { "on" } else { "off" }),
+                    }
+
+                    Ok(())
+                }
+            },
+            None => player.shuffle_toggle().await,
+        };
+    }
+
+    if let Some(repeat) = matches.subcommand_matches("repeat") {
+        return match repeat.get_one::<RepeatOperation>("mode") {
+            Some(mode) => match mode {
+                RepeatOperation::On => player.repeat_on().await,
+                RepeatOperation::Off => player.repeat_off().await,
+                RepeatOperation::Track => player.repeat_track().await,
+                RepeatOperation::Status => {
+                    let state = match player.repeat_state().await? {
+                        RepeatState::Off => "off",
+                        RepeatState::Context => "on",
+                        RepeatState::Track => "track",
+                    };
+
+                    match repeat.get_flag("json") {
+                        true => println!("{}", serde_json::json!({ "repeat": state })),
+                        false => println!("{}", state),
+                    }
+
+                    Ok(())
+                }
+            },
+            None => player.repeat_toggle().await,
+        };
     }
 
     Ok(())
 }
 
 /// Get the settings for command parsing
-fn command() -> Command {
+pub fn command() -> Command {
     Command::new("sc")
         .version(env!("CARGO_PKG_VERSION"))
         .about("Spotify CLI controller")
+        .after_help(error::EXIT_CODES)
         .arg_required_else_help(true)
         .subcommand(
             Command::new("current")
                 .about("Output current track")
-                .alias("cu"),
+                .alias("cu")
+                .after_help(format!("Available placeholders for --format: {}", format::PLACEHOLDERS))
+                .args([
+                    Arg::new("art")
+                        .help("Display the cover art of the current track")
+                        .long("art")
+                        .short('a')
+                        .action(ArgAction::SetTrue),
+                    Arg::new("art-backend")
+                        .help("How to display the cover art, detected if not specified [ascii | sixel | kitty]")
+                        .long("art-backend")
+                        .requires("art")
+                        .action(ArgAction::Set)
+                        .value_parser(art_backend_parser),
+                    Arg::new("follow")
+                        .help("Keep updating the current track and its progress on a single line")
+                        .long("follow")
+                        .short('f')
+                        .conflicts_with("art")
+                        .action(ArgAction::SetTrue),
+                    Arg::new("format")
+                        .help("The format of the output line")
+                        .long("format")
+                        .conflicts_with_all(["art", "json"])
+                        .action(ArgAction::Set),
+                    Arg::new("json")
+                        .help("Output the playback status as JSON")
+                        .long("json")
+                        .conflicts_with("art")
+                        .action(ArgAction::SetTrue),
+                    Arg::new("raw")
+                        .help("Output the full playback state from spotify as JSON, unlike --json its structure isn't stable")
+                        .long("raw")
+                        .conflicts_with_all(["art", "follow", "format", "json", "output", "remaining", "id"])
+                        .action(ArgAction::SetTrue),
+                    Arg::new("id")
+                        .help("Output only the Spotify ID of the current item, nothing if nothing is playing")
+                        .long("id")
+                        .conflicts_with_all(["art", "follow", "format", "json", "output", "remaining"])
+                        .action(ArgAction::SetTrue),
+                    Arg::new("remaining")
+                        .help("Show the time remaining until the track ends instead of its progress")
+                        .long("remaining")
+                        .short('r')
+                        .conflicts_with_all(["art", "format", "json", "json-lines"])
+                        .action(ArgAction::SetTrue),
+                    Arg::new("json-lines")
+                        .help("Print the playback status as a JSON object on its own line whenever it changes")
+                        .long("json-lines")
+                        .requires("follow")
+                        .conflicts_with_all(["format", "json", "output"])
+                        .action(ArgAction::SetTrue),
+                    Arg::new("output")
+                        .help("Write the output to this file instead, replacing it atomically on every update")
+                        .long("output")
+                        .short('o')
+                        .conflicts_with("art")
+                        .action(ArgAction::Set)
+                        .value_parser(value_parser!(PathBuf)),
+                ]),
+        )
+        .subcommand(
+            Command::new("now")
+                .about("Output the current track as fast as possible, e.g. for status bars")
+                .after_help("Only makes a single request and never selects a device. Outputs an empty line if nothing is playing"),
+        )
+        .subcommand(
+            Command::new("bar")
+                .about("Output a single status line for status bars")
+                .alias("ba")
+                .after_help(format!(
+                    "Never prompts and always exits successfully. Available placeholders: {}",
+                    format::PLACEHOLDERS
+                ))
+                .args([
+                    Arg::new("format")
+                        .help("The format of the status line [default: \"{icon} {title} - {artist}\"]")
+                        .long("format")
+                        .short('f')
+                        .action(ArgAction::Set),
+                    Arg::new("placeholder")
+                        .help("The text output when nothing is playing [default: \"\"]")
+                        .long("placeholder")
+                        .action(ArgAction::Set),
+                ]),
+        )
+        .subcommand(
+            Command::new("lyrics")
+                .about("Output the lyrics of the current track")
+                .alias("ly")
+                .after_help("Lyrics are fetched from lrclib.net, unless another provider is configured")
+                .args([Arg::new("synced")
+                    .help("Output synced lyrics, highlighting the current line")
+                    .long("synced")
+                    .short('s')
+                    .action(ArgAction::SetTrue)]),
+        )
+        .subcommand(
+            Command::new("open")
+                .about("Open the current track in the browser or the Spotify app")
+                .alias("op")
+                .args([Arg::new("uri")
+                    .help("Open this Spotify URI or link instead, e.g. spotify:track:4PTG3Z6ehGkBFwjybzWkR8")
+                    .long("uri")
+                    .short('u')
+                    .action(ArgAction::Set)
+                    .value_parser(uri_parser)]),
+        )
+        .subcommand(
+            Command::new("wait-track-change")
+                .about("Wait until the current track changes, then output the new track")
+                .alias("wt")
+                .after_help("Also returns once playback is paused or stopped. The playback is checked every 2 seconds, unless another poll interval is configured")
+                .args([Arg::new("wait-timeout")
+                    .help("Give up after this many seconds")
+                    .long("wait-timeout")
+                    .action(ArgAction::Set)
+                    .value_parser(seconds_parser)]),
         )
         .subcommand(Command::new("pause").about("Pause playback").alias("pa"))
+        .subcommand(
+            Command::new("stop")
+                .about("Pause playback and go back to the beginning of the current track")
+                .alias("so")
+                .after_help("Spotify can't stop playback, so this is the closest equivalent"),
+        )
         .subcommand(Command::new("resume").about("Resume playback").alias("re"))
         .subcommand(
             Command::new("toggle")
@@ -199,20 +1193,26 @@ fn command() -> Command {
             Command::new("volume")
                 .about("Control volume")
                 .alias("vo")
-                .arg(
+                .after_help("Outputs the current volume if no amount is supplied. A bare + or - changes the volume by the configured volume step. A trailing % changes the volume relative to the current volume, e.g. +10% is 10% louder")
+                .args([
                     Arg::new("amount")
-                        .help("Set or change volume in percent [50 | +5 | -5]")
+                        .help("Set or change volume in percent [50 | +5 | -5 | + | - | +10% | -10% | get] (optional)")
+                        .required(false)
                         .allow_hyphen_values(true)
                         .action(ArgAction::Set)
                         .value_parser(volume_parser),
-                )
-                .arg_required_else_help(true),
+                    Arg::new("json")
+                        .help("Output the current volume as JSON")
+                        .long("json")
+                        .action(ArgAction::SetTrue),
+                ]),
         )
         .subcommand(
             Command::new("play")
                 .about("Play first matching content")
                 .alias("pl")
-                .group(ArgGroup::new("type").required(true).multiple(false))
+                .after_help("Plays tracks, if no type is specified")
+                .group(ArgGroup::new("type").multiple(true))
                 .args([
                     Arg::new("track")
                         .help("Play tracks")
@@ -250,9 +1250,58 @@ fn command() -> Command {
                         .long("episode")
                         .short('e')
                         .action(ArgAction::SetTrue),
+                    Arg::new("market")
+                        .help("Only include content available in this market [US | DE | ...]")
+                        .long("market")
+                        .short('m')
+                        .action(ArgAction::Set)
+                        .value_parser(config::parse_market),
+                    Arg::new("clean")
+                        .help("Skip explicit tracks and episodes")
+                        .long("clean")
+                        .action(ArgAction::SetTrue),
+                    Arg::new("all")
+                        .help("Queue the remaining results after the first one, only tracks and episodes can be queued")
+                        .long("all")
+                        .action(ArgAction::SetTrue),
+                    Arg::new("count")
+                        .help("The maximum amount of results to play when using --all")
+                        .long("count")
+                        .short('c')
+                        .requires("all")
+                        .action(ArgAction::Set)
+                        .value_parser(value_parser!(u32).range(1..=50)),
+                    wait_arg(),
+                    Arg::new("shuffle")
+                        .help("Play in shuffle mode, only applies to albums, playlists and artists")
+                        .long("shuffle")
+                        .short('S')
+                        .action(ArgAction::SetTrue),
+                    Arg::new("top")
+                        .help("Play the top tracks of the first matching artist instead of the artist")
+                        .long("top")
+                        .requires("artist")
+                        .conflicts_with_all(["all", "next", "shuffle", "wait"])
+                        .action(ArgAction::SetTrue),
+                    Arg::new("in-context")
+                        .help("Play the first matching track within its album, so the rest of the album plays afterwards")
+                        .long("in-context")
+                        .conflicts_with_all(["all", "next", "shuffle", "top", "playlist", "album", "artist", "show", "episode"])
+                        .action(ArgAction::SetTrue),
+                    Arg::new("next")
+                        .help("Queue the first result to play after the current track instead, only tracks and episodes can be queued")
+                        .long("next")
+                        .short('n')
+                        .conflicts_with_all(["all", "shuffle", "wait"])
+                        .action(ArgAction::SetTrue),
+                    Arg::new("clipboard")
+                        .help("Play the Spotify link or URI in the clipboard instead of searching")
+                        .long("clipboard")
+                        .conflicts_with_all(["content", "type", "market", "clean", "all", "wait", "shuffle", "top", "next", "in-context"])
+                        .action(ArgAction::SetTrue),
                     Arg::new("content")
                         .help("Content to play")
-                        .required(true)
+                        .required_unless_present("clipboard")
                         .action(ArgAction::Set),
                 ])
                 .arg_required_else_help(true),
@@ -261,14 +1310,26 @@ fn command() -> Command {
             Command::new("search")
                 .about("Search content")
                 .alias("se")
-                .group(ArgGroup::new("type").required(true).multiple(false))
+                .after_help("Searches for tracks, if no type is specified")
+                .group(ArgGroup::new("type").multiple(true))
                 .args([
                     Arg::new("count")
-                        .help("The amount of items to search")
+                        .help("The amount of items to search per type, 0 searches for as many as --all")
                         .long("count")
                         .short('c')
                         .action(ArgAction::Set)
                         .value_parser(value_parser!(u32)),
+                    Arg::new("all")
+                        .help("Search for up to 200 items per type")
+                        .long("all")
+                        .conflicts_with("count")
+                        .action(ArgAction::SetTrue),
+                    Arg::new("select")
+                        .help("Play the nth result directly instead of displaying a selection")
+                        .long("select")
+                        .short('S')
+                        .action(ArgAction::Set)
+                        .value_parser(value_parser!(u32).range(1..)),
                     Arg::new("track")
                         .help("Search for tracks")
                         .group("type")
@@ -305,6 +1366,100 @@ fn command() -> Command {
                         .long("episode")
                         .short('e')
                         .action(ArgAction::SetTrue),
+                    Arg::new("market")
+                        .help("Only include content available in this market [US | DE | ...]")
+                        .long("market")
+                        .short('m')
+                        .action(ArgAction::Set)
+                        .value_parser(config::parse_market),
+                    Arg::new("clean")
+                        .help("Skip explicit tracks and episodes")
+                        .long("clean")
+                        .action(ArgAction::SetTrue),
+                    Arg::new("dedup")
+                        .help("Collapse tracks with the same name and artist, keeping the most popular one")
+                        .long("dedup")
+                        .action(ArgAction::SetTrue),
+                    Arg::new("play")
+                        .help("Play the selected item without asking whether to queue it")
+                        .long("play")
+                        .conflicts_with("queue")
+                        .action(ArgAction::SetTrue),
+                    Arg::new("queue")
+                        .help("Add the selected item to the queue instead of playing it")
+                        .long("queue")
+                        .short('q')
+                        .action(ArgAction::SetTrue),
+                    Arg::new("json")
+                        .help("Output the results as JSON instead of displaying a selection")
+                        .long("json")
+                        .conflicts_with("select")
+                        .action(ArgAction::SetTrue),
+                    Arg::new("markdown")
+                        .help("Output the results as a Markdown list of links instead of displaying a selection")
+                        .long("markdown")
+                        .conflicts_with_all(["select", "json"])
+                        .action(ArgAction::SetTrue),
+                    Arg::new("detailed")
+                        .help("Show the track count and total duration of albums and playlists, which is slower")
+                        .long("detailed")
+                        .conflicts_with("json")
+                        .action(ArgAction::SetTrue),
+                    Arg::new("multi")
+                        .help("Select several items and queue them, the first one is played instead when using --play")
+                        .long("multi")
+                        .conflicts_with_all(["select", "json"])
+                        .action(ArgAction::SetTrue),
+                    Arg::new("content")
+                        .help("Content to search for")
+                        .required(true)
+                        .action(ArgAction::Set),
+                ])
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("follow")
+                .about("Search for an artist or playlist and follow the selected item")
+                .alias("fo")
+                .group(ArgGroup::new("type").required(true).multiple(false))
+                .args([
+                    Arg::new("playlist")
+                        .help("Search for playlists")
+                        .group("type")
+                        .long("playlist")
+                        .short('p')
+                        .action(ArgAction::SetTrue),
+                    Arg::new("artist")
+                        .help("Search for artists")
+                        .group("type")
+                        .long("artist")
+                        .short('A')
+                        .action(ArgAction::SetTrue),
+                    Arg::new("content")
+                        .help("Content to search for")
+                        .required(true)
+                        .action(ArgAction::Set),
+                ])
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("unfollow")
+                .about("Search for an artist or playlist and unfollow the selected item")
+                .alias("uf")
+                .group(ArgGroup::new("type").required(true).multiple(false))
+                .args([
+                    Arg::new("playlist")
+                        .help("Search for playlists")
+                        .group("type")
+                        .long("playlist")
+                        .short('p')
+                        .action(ArgAction::SetTrue),
+                    Arg::new("artist")
+                        .help("Search for artists")
+                        .group("type")
+                        .long("artist")
+                        .short('A')
+                        .action(ArgAction::SetTrue),
                     Arg::new("content")
                         .help("Content to search for")
                         .required(true)
@@ -312,66 +1467,456 @@ fn command() -> Command {
                 ])
                 .arg_required_else_help(true),
         )
+        .subcommand(
+            Command::new("create-playlist")
+                .about("Create a new empty playlist in your library")
+                .alias("cp")
+                .after_help("Playlists are private, if --public is not specified")
+                .args([
+                    Arg::new("name")
+                        .help("The name of the playlist")
+                        .required(true)
+                        .action(ArgAction::Set),
+                    Arg::new("description")
+                        .help("The description of the playlist")
+                        .long("description")
+                        .action(ArgAction::Set),
+                    Arg::new("public")
+                        .help("Make the playlist public")
+                        .long("public")
+                        .conflicts_with("private")
+                        .action(ArgAction::SetTrue),
+                    Arg::new("private")
+                        .help("Make the playlist private")
+                        .long("private")
+                        .action(ArgAction::SetTrue),
+                ])
+                .arg_required_else_help(true),
+        )
         .subcommand(
             Command::new("library")
                 .about("Play playlist from users library")
                 .alias("li")
                 .after_help(
-                    "Displays selection from all playlists from library, if no name is specified",
+                    "Displays selection from all matching playlists from library, if no name is specified",
                 )
-                .args([Arg::new("name")
-                    .help("Play first playlist from library matching this name (optional)")
-                    .required(false)
-                    .action(ArgAction::Set)]),
+                .args([
+                    Arg::new("name")
+                        .help("Play the playlist from library best matching this name (optional)")
+                        .required(false)
+                        .action(ArgAction::Set),
+                    Arg::new("filter")
+                        .help("Only display playlists matching this name")
+                        .long("filter")
+                        .short('f')
+                        .conflicts_with("name")
+                        .action(ArgAction::Set),
+                    Arg::new("mine")
+                        .help("Only include playlists you own, excluding followed playlists")
+                        .long("mine")
+                        .short('m')
+                        .conflicts_with("owner")
+                        .action(ArgAction::SetTrue),
+                    Arg::new("owner")
+                        .help("Only include playlists whose owner matches this name")
+                        .long("owner")
+                        .short('o')
+                        .action(ArgAction::Set),
+                    Arg::new("limit")
+                        .help("The maximum amount of playlists to fetch from the library, all are fetched by default")
+                        .long("limit")
+                        .short('l')
+                        .action(ArgAction::Set)
+                        .value_parser(value_parser!(u32).range(1..)),
+                    Arg::new("offset")
+                        .help("The amount of playlists to skip when fetching the library")
+                        .long("offset")
+                        .action(ArgAction::Set)
+                        .value_parser(value_parser!(u32)),
+                    Arg::new("start")
+                        .help("Start playing at the track with this position in the playlist")
+                        .long("start")
+                        .short('s')
+                        .action(ArgAction::Set)
+                        .value_parser(value_parser!(u32).range(1..)),
+                    Arg::new("json")
+                        .help("Output the matching playlists as JSON instead of playing one")
+                        .long("json")
+                        .conflicts_with("name")
+                        .action(ArgAction::SetTrue),
+                    Arg::new("markdown")
+                        .help("Output the matching playlists as a Markdown list of links instead of playing one")
+                        .long("markdown")
+                        .conflicts_with_all(["name", "json"])
+                        .action(ArgAction::SetTrue),
+                    Arg::new("detailed")
+                        .help("Show the track count and total duration of the playlists, which is slower")
+                        .long("detailed")
+                        .conflicts_with_all(["json", "name"])
+                        .action(ArgAction::SetTrue),
+                ]),
         )
         .subcommand(
-            Command::new("device")
-                .about("Select a playback device")
-                .alias("de")
-                .after_help(
-                    "Displays selection from all available playback devices, if no name is specified",
-                )
-                .args([Arg::new("name")
-                    .help("Selects first available playback device matching this name (optional)")
-                    .required(false)
-                    .action(ArgAction::Set)]),
+            Command::new("whoami")
+                .about("Output the authenticated user")
+                .alias("wh")
+                .args([Arg::new("json")
+                    .help("Output the user as JSON")
+                    .long("json")
+                    .action(ArgAction::SetTrue)]),
         )
-        .subcommand(Command::new("next").about("Skip current track").alias("ne"))
         .subcommand(
-            Command::new("prev")
-                .about("Play previous track")
-                .alias("pr"),
+            Command::new("recent")
+                .about("Output recently played tracks")
+                .alias("rc")
+                .args([
+                    Arg::new("count")
+                        .help("The amount of tracks to output")
+                        .long("count")
+                        .short('c')
+                        .action(ArgAction::Set)
+                        .value_parser(value_parser!(u32).range(1..=50)),
+                    Arg::new("since")
+                        .help("Only output tracks played after this time, either ago or absolute [30m | 2h | 1d | 2024-05-01T08:00:00Z]")
+                        .long("since")
+                        .short('s')
+                        .action(ArgAction::Set)
+                        .value_parser(since_parser),
+                    Arg::new("play")
+                        .help("Select a recently played track to play again")
+                        .long("play")
+                        .short('p')
+                        .action(ArgAction::SetTrue),
+                    Arg::new("markdown")
+                        .help("Output the tracks as a Markdown list of links")
+                        .long("markdown")
+                        .conflicts_with("play")
+                        .action(ArgAction::SetTrue),
+                ]),
         )
         .subcommand(
-            Command::new("shuffle")
-                .about("Control shuffle mode")
-                .alias("sh")
-                .after_help("Toggles between on/off if no mode is supplied")
-                .args([Arg::new("mode")
-                    .help("The shuffle mode [on | off] (optional)")
-                    .required(false)
-                    .action(ArgAction::Set)
-                    .value_parser(shuffle_parser)]),
+            Command::new("queue")
+                .about("Output the playback queue")
+                .alias("qu")
+                .after_help("Spotify only returns the next few items, including the remaining items of the playing album or playlist")
+                .args([Arg::new("save")
+                    .help("Save the current track and the queue to a new private playlist with this name")
+                    .long("save")
+                    .short('s')
+                    .action(ArgAction::Set)]),
         )
         .subcommand(
-            Command::new("repeat")
-                .about("Control repeat mode")
-                .alias("rp")
-                .after_help("Toggles between on/off if no mode is supplied")
-                .args([Arg::new("mode")
-                    .help("The repeat mode [on | off | track] (optional)")
-                    .required(false)
+            Command::new("top")
+                .about("Display top tracks or artists and play selected item")
+                .alias("tp")
+                .group(ArgGroup::new("type").required(true).multiple(false))
+                .args([
+                    Arg::new("tracks")
+                        .help("Display top tracks")
+                        .group("type")
+                        .long("tracks")
+                        .short('t')
+                        .action(ArgAction::SetTrue),
+                    Arg::new("artists")
+                        .help("Display top artists")
+                        .group("type")
+                        .long("artists")
+                        .short('A')
+                        .action(ArgAction::SetTrue),
+                    Arg::new("count")
+                        .help("The amount of items to display")
+                        .long("count")
+                        .short('c')
+                        .action(ArgAction::Set)
+                        .value_parser(value_parser!(u32).range(1..=50)),
+                    Arg::new("time-range")
+                        .help("The time frame to compute the top items over [short | medium | long]")
+                        .long("time-range")
+                        .short('r')
+                        .action(ArgAction::Set)
+                        .value_parser(time_range_parser),
+                    Arg::new("markdown")
+                        .help("Output the top items as a Markdown list of links instead of playing one")
+                        .long("markdown")
+                        .action(ArgAction::SetTrue),
+                ])
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("radio")
+                .about("Play recommendations based on the current track")
+                .alias("ra")
+                .args([Arg::new("count")
+                    .help("The amount of recommended tracks to play")
+                    .long("count")
+                    .short('c')
                     .action(ArgAction::Set)
-                    .value_parser(repeat_parser)]),
+                    .value_parser(value_parser!(u32).range(1..=100))]),
         )
-        .next_help_heading("Settings")
-        .args([Arg::new("authorize")
-            .long("authorize")
-            .help("Run the authorization process")
-            .exclusive(true)
-            .action(ArgAction::SetTrue)])
-}
-
+        .subcommand(
+            Command::new("autoplay")
+                .about("Keep playing by queueing recommendations whenever the queue runs low")
+                .alias("ap")
+                .after_help("Recommendations are based on the current and recently played tracks. Runs until Ctrl-C is pressed")
+                .args([
+                    Arg::new("threshold")
+                        .help("Queue recommendations once fewer items than this are coming up [default: 3]")
+                        .long("threshold")
+                        .short('t')
+                        .action(ArgAction::Set)
+                        .value_parser(value_parser!(u32).range(1..=20)),
+                    Arg::new("seeds")
+                        .help("The amount of recently played tracks recommendations are based on [default: 5]")
+                        .long("seeds")
+                        .short('s')
+                        .action(ArgAction::Set)
+                        .value_parser(value_parser!(u32).range(1..=5)),
+                    Arg::new("count")
+                        .help("The amount of recommended tracks queued at once [default: 10]")
+                        .long("count")
+                        .short('c')
+                        .action(ArgAction::Set)
+                        .value_parser(value_parser!(u32).range(1..=100)),
+                ]),
+        )
+        .subcommand(
+            Command::new("repl")
+                .about("Run many commands interactively without reloading the session")
+                .alias("rl")
+                .after_help("Enter commands without the leading \"sc\", e.g. \"play -t foo\". Enter \"quit\" to exit"),
+        )
+        .subcommand(
+            Command::new("batch")
+                .about("Run one command per line read from stdin")
+                .alias("bt")
+                .after_help("Lines are entered without the leading \"sc\", e.g. \"sc batch < commands.txt\". Empty lines and lines starting with \"#\" are skipped")
+                .args([Arg::new("continue-on-error")
+                    .help("Log failing commands and continue instead of stopping")
+                    .long("continue-on-error")
+                    .action(ArgAction::SetTrue)]),
+        )
+        .subcommand(
+            Command::new("tui")
+                .about("Browse playlists and control playback in a terminal UI")
+                .alias("tu")
+                .after_help(
+                    "Keybindings: j/k navigate, enter play, space toggle, n/p next/prev, +/- volume, q quit",
+                ),
+        )
+        .subcommands(daemon_command())
+        .subcommand(
+            Command::new("device")
+                .about("Select a playback device")
+                .alias("de")
+                .after_help(
+                    "Displays selection from all available playback devices, if no name is specified.\n\nSpotify has no way of disconnecting a device, so --disconnect only pauses playback, the device stays available and the next command selects a playback device again",
+                )
+                .args([
+                    Arg::new("name")
+                        .help("Selects the available playback device with this ID or best matching this name (optional)")
+                        .required(false)
+                        .action(ArgAction::Set),
+                    Arg::new("list")
+                        .help("Output available playback devices without selecting one")
+                        .long("list")
+                        .short('l')
+                        .conflicts_with("name")
+                        .action(ArgAction::SetTrue),
+                    Arg::new("json")
+                        .help("Output available playback devices as JSON without selecting one")
+                        .long("json")
+                        .conflicts_with("name")
+                        .action(ArgAction::SetTrue),
+                    Arg::new("disconnect")
+                        .help("Pause playback and stop using the current playback device")
+                        .long("disconnect")
+                        .conflicts_with_all(["name", "list", "json"])
+                        .action(ArgAction::SetTrue),
+                    Arg::new("play")
+                        .help("Start playing on the selected device, instead of keeping the playback state")
+                        .long("play")
+                        .short('p')
+                        .conflicts_with_all(["list", "json", "disconnect"])
+                        .action(ArgAction::SetTrue),
+                    Arg::new("resume")
+                        .help("Resume playback on the selected device, starting the album or playlist played last if nothing is left to resume")
+                        .long("resume")
+                        .short('r')
+                        .conflicts_with_all(["list", "json", "disconnect", "play"])
+                        .action(ArgAction::SetTrue),
+                ]),
+        )
+        .subcommand(
+            Command::new("next")
+                .about("Skip current track")
+                .alias("ne")
+                .args([wait_arg()]),
+        )
+        .subcommand(
+            Command::new("prev")
+                .about("Play previous track")
+                .alias("pr")
+                .args([
+                    wait_arg(),
+                    Arg::new("smart")
+                        .help("Restart the current track instead, if it played for more than 3 seconds")
+                        .long("smart")
+                        .short('s')
+                        .action(ArgAction::SetTrue),
+                ]),
+        )
+        .subcommand(
+            Command::new("restart")
+                .about("Play current track from the beginning")
+                .alias("rs"),
+        )
+        .subcommand(
+            Command::new("shuffle")
+                .about("Control shuffle mode")
+                .alias("sh")
+                .after_help("Toggles between on/off if no mode is supplied. Use status to output the current mode without changing it")
+                .args([
+                    Arg::new("mode")
+                        .help("The shuffle mode [on | off | status] (optional)")
+                        .required(false)
+                        .action(ArgAction::Set)
+                        .value_parser(shuffle_parser),
+                    Arg::new("json")
+                        .help("Output the current mode as JSON, used with status")
+                        .long("json")
+                        .action(ArgAction::SetTrue),
+                ]),
+        )
+        .subcommand(
+            Command::new("repeat")
+                .about("Control repeat mode")
+                .alias("rp")
+                .after_help("Toggles between on/off if no mode is supplied. Use status to output the current mode without changing it")
+                .args([
+                    Arg::new("mode")
+                        .help("The repeat mode [on | off | track | status] (optional)")
+                        .required(false)
+                        .action(ArgAction::Set)
+                        .value_parser(repeat_parser),
+                    Arg::new("json")
+                        .help("Output the current mode as JSON, used with status")
+                        .long("json")
+                        .action(ArgAction::SetTrue),
+                ]),
+        )
+        .next_help_heading("Settings")
+        .args([
+            Arg::new("authorize")
+                .long("authorize")
+                .help("Run the authorization process")
+                .action(ArgAction::SetTrue),
+            Arg::new("force-reauth")
+                .long("force-reauth")
+                .help("Discard the cached tokens and re-authorize before running the command")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("authorize"),
+            Arg::new("callback-port")
+                .long("callback-port")
+                .help("The port of the local authorization callback server [default: 8080]")
+                .action(ArgAction::Set)
+                .value_parser(value_parser!(u16).range(1..)),
+            Arg::new("manual-auth")
+                .long("manual-auth")
+                .help("Paste the URL opened after authorizing, instead of running the local callback server")
+                .action(ArgAction::SetTrue),
+            Arg::new("callback-timeout")
+                .long("callback-timeout")
+                .help("How long the callback server waits for the callback, before asking to paste the URL, in seconds [default: 120]")
+                .conflicts_with("manual-auth")
+                .action(ArgAction::Set)
+                .value_parser(seconds_parser),
+            Arg::new("no-open")
+                .long("no-open")
+                .help("Only print the authorization link instead of opening it in a browser, the default over SSH")
+                .action(ArgAction::SetTrue),
+            Arg::new("device")
+                .long("device")
+                .short('d')
+                .help("Select the available playback device with this ID or best matching this name")
+                .global(true)
+                .action(ArgAction::Set),
+            Arg::new("no-color")
+                .long("no-color")
+                .help("Disable colorized output")
+                .global(true)
+                .action(ArgAction::SetTrue),
+            Arg::new("poll-interval")
+                .long("poll-interval")
+                .help("How often current --follow, the terminal UI and the daemon poll spotify, in seconds [default: 2]")
+                .global(true)
+                .action(ArgAction::Set)
+                .value_parser(value_parser!(f64)),
+            Arg::new("dry-run")
+                .long("dry-run")
+                .help("Print what would be changed instead of changing it")
+                .global(true)
+                .action(ArgAction::SetTrue),
+            Arg::new("timeout")
+                .long("timeout")
+                .help("How long a single request to spotify may take, in seconds [default: 10]")
+                .global(true)
+                .action(ArgAction::Set)
+                .value_parser(seconds_parser),
+            Arg::new("no-cache")
+                .long("no-cache")
+                .help("Always search spotify, instead of reusing recent search results")
+                .global(true)
+                .action(ArgAction::SetTrue),
+            Arg::new("yes")
+                .long("yes")
+                .short('y')
+                .help("Don't ask for confirmation before destructive actions, e.g. unfollowing")
+                .global(true)
+                .action(ArgAction::SetTrue),
+            Arg::new("verbose")
+                .long("verbose")
+                .short('v')
+                .help("Log what is going on to stderr, pass twice for more details")
+                .global(true)
+                .action(ArgAction::Count),
+        ])
+}
+
+/// The argument for waiting until spotify has applied a change
+fn wait_arg() -> Arg {
+    Arg::new("wait")
+        .help("Only return once spotify has applied the change")
+        .long("wait")
+        .short('w')
+        .action(ArgAction::SetTrue)
+}
+
+/// Get a snapshot of the current playback, if the user wants to wait for a change
+async fn wait_snapshot(
+    player: &mut SpotifyPlayer,
+    matches: &ArgMatches,
+) -> Option<PlaybackSnapshot> {
+    match matches.get_flag("wait") {
+        true => Some(player.snapshot().await),
+        false => None,
+    }
+}
+
+/// The daemon subcommand, only available on Linux builds with MPRIS support
+fn daemon_command() -> Option<Command> {
+    if !cfg!(all(target_os = "linux", feature = "mpris")) {
+        return None;
+    }
+
+    Some(
+        Command::new("daemon")
+            .about(
+                "Expose an MPRIS interface, so media keys and desktop widgets can control Spotify",
+            )
+            .alias("da"),
+    )
+}
+
 /// A custom parser for volume arguments
 fn volume_parser(arg: &str) -> Result<VolumeOperation, String> {
     fn parse_num(str: &str) -> Result<u8, String> {
@@ -381,31 +1926,48 @@ fn volume_parser(arg: &str) -> Result<VolumeOperation, String> {
 
         match num {
             0..=100 => Ok(num),
-            _ => Err(format!("Please provide a volume value between 0 and 100")),
+            _ => Err("Please provide a volume value between 0 and 100".to_string()),
         }
     }
 
     if arg.starts_with("+") {
+        // A bare sign steps by the configured amount
         if arg.len() < 2 {
-            return Err(format!("Please provide a value to increase the volume by"));
+            return Ok(VolumeOperation::StepUp);
         }
 
         let arg: String = arg.chars().skip(1).collect();
 
+        // A trailing percent sign changes the volume relative to the current volume
+        if let Some(percent) = arg.strip_suffix('%') {
+            return Ok(VolumeOperation::IncreasePercent(parse_num(percent)?));
+        }
+
         return Ok(VolumeOperation::Increase(parse_num(&arg)?));
     }
 
     if arg.starts_with("-") {
+        // A bare sign steps by the configured amount
         if arg.len() < 2 {
-            return Err(format!("Please provide a value to decrease the volume by"));
+            return Ok(VolumeOperation::StepDown);
         }
 
         let arg: String = arg.chars().skip(1).collect();
 
+        if let Some(percent) = arg.strip_suffix('%') {
+            return Ok(VolumeOperation::DecreasePercent(parse_num(percent)?));
+        }
+
         return Ok(VolumeOperation::Decrease(parse_num(&arg)?));
     }
 
-    return Ok(VolumeOperation::Set(parse_num(arg)?));
+    if arg.eq_ignore_ascii_case("get") {
+        return Ok(VolumeOperation::Get);
+    }
+
+    Ok(VolumeOperation::Set(parse_num(
+        arg.strip_suffix('%').unwrap_or(arg),
+    )?))
 }
 
 /// A custon parser for shuffle arguments
@@ -413,7 +1975,8 @@ fn shuffle_parser(arg: &str) -> Result<ShuffleOperation, String> {
     match arg.to_lowercase().as_str() {
         "on" => Ok(ShuffleOperation::On),
         "off" => Ok(ShuffleOperation::Off),
-        _ => Err(format!("Not a valid shuffle mode")),
+        "status" => Ok(ShuffleOperation::Status),
+        _ => Err("Not a valid shuffle mode".to_string()),
     }
 }
 
@@ -423,25 +1986,742 @@ fn repeat_parser(arg: &str) -> Result<RepeatOperation, String> {
         "on" => Ok(RepeatOperation::On),
         "off" => Ok(RepeatOperation::Off),
         "track" => Ok(RepeatOperation::Track),
-        _ => Err(format!("Not a valid repeat mode")),
-    }
-}
-
-/// Get the SearchType from argument matches
-fn type_matches(matches: &ArgMatches) -> Option<SearchType> {
-    if matches.get_flag("track") {
-        Some(SearchType::Track)
-    } else if matches.get_flag("playlist") {
-        Some(SearchType::Playlist)
-    } else if matches.get_flag("album") {
-        Some(SearchType::Album)
-    } else if matches.get_flag("artist") {
-        Some(SearchType::Artist)
-    } else if matches.get_flag("show") {
-        Some(SearchType::Show)
-    } else if matches.get_flag("episode") {
-        Some(SearchType::Episode)
-    } else {
-        None
+        "status" => Ok(RepeatOperation::Status),
+        _ => Err("Not a valid repeat mode".to_string()),
+    }
+}
+
+/// A custom parser for cover art backend arguments
+fn art_backend_parser(arg: &str) -> Result<art::Backend, String> {
+    match arg.to_lowercase().as_str() {
+        "ascii" => Ok(art::Backend::Ascii),
+        "sixel" => Ok(art::Backend::Sixel),
+        "kitty" => Ok(art::Backend::Kitty),
+        _ => Err("Not a valid cover art backend".to_string()),
+    }
+}
+
+/// A custom parser for Spotify URIs, converting them to open.spotify.com links
+fn uri_parser(arg: &str) -> Result<String, String> {
+    if arg.starts_with("https://") {
+        return match reqwest::Url::parse(arg) {
+            Ok(url) if url.host_str() == Some(SPOTIFY_LINK_HOST) => Ok(arg.to_string()),
+            _ => Err(format!("\"{}\" is not a valid Spotify link", arg)),
+        };
+    }
+
+    // URIs look like "spotify:track:<id>", links like "https://open.spotify.com/track/<id>"
+    match arg.strip_prefix("spotify:") {
+        Some(path) if !path.is_empty() => Ok(format!(
+            "https://open.spotify.com/{}",
+            path.replace(':', "/")
+        )),
+        _ => Err(format!("\"{}\" is not a valid Spotify URI or link", arg)),
+    }
+}
+
+/// Get the Spotify URI of the first Spotify link or URI in the clipboard
+fn clipboard_uri() -> Result<String> {
+    let text = arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.get_text())
+        .context("Failed reading the clipboard")?;
+
+    text.split_whitespace()
+        .find_map(spotify_uri)
+        .context("The clipboard doesn't contain a Spotify link or URI, copy one using \"Share\" in Spotify first")
+}
+
+/// Convert a Spotify link or URI to a URI, if it is one
+fn spotify_uri(text: &str) -> Option<String> {
+    if text
+        .strip_prefix("spotify:")
+        .is_some_and(|rest| !rest.is_empty())
+    {
+        return Some(text.to_string());
+    }
+
+    // Links look like "https://open.spotify.com/intl-de/track/<id>?si=<share id>"
+    let url = reqwest::Url::parse(text).ok()?;
+
+    if url.host_str() != Some(SPOTIFY_LINK_HOST) {
+        return None;
+    }
+
+    let mut segments = url.path_segments()?.filter(|s| !s.starts_with("intl-"));
+
+    match (segments.next(), segments.next()) {
+        (Some(item_type), Some(id)) if !item_type.is_empty() && !id.is_empty() => {
+            Some(format!("spotify:{}:{}", item_type, id))
+        }
+        _ => None,
+    }
+}
+
+/// A custom parser for arguments in seconds, which have to be positive
+fn seconds_parser(arg: &str) -> Result<Duration, String> {
+    arg.parse::<f64>()
+        .map_err(|_| format!("\"{}\" is not a valid number", arg))
+        .and_then(config::parse_timeout)
+}
+
+/// A custom parser for points in time, either an RFC 3339 time or a duration ago like 1h30m
+fn since_parser(arg: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(arg) {
+        return Ok(time.with_timezone(&Utc));
+    }
+
+    let invalid = || {
+        format!(
+            "\"{}\" is neither a duration like 2h nor an RFC 3339 time",
+            arg
+        )
+    };
+    let mut ago = chrono::Duration::zero();
+    let mut number = String::new();
+
+    for c in arg.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+
+        let amount = number.parse::<i64>().map_err(|_| invalid())?;
+        number.clear();
+
+        ago += match c {
+            's' => chrono::Duration::try_seconds(amount),
+            'm' => chrono::Duration::try_minutes(amount),
+            'h' => chrono::Duration::try_hours(amount),
+            'd' => chrono::Duration::try_days(amount),
+            _ => None,
+        }
+        .ok_or_else(invalid)?;
+    }
+
+    // Every number needs a unit
+    if !number.is_empty() || ago.is_zero() {
+        return Err(invalid());
+    }
+
+    Utc::now().checked_sub_signed(ago).ok_or_else(invalid)
+}
+
+/// A custom parser for time range arguments
+fn time_range_parser(arg: &str) -> Result<TimeRange, String> {
+    match arg.to_lowercase().as_str() {
+        "short" => Ok(TimeRange::ShortTerm),
+        "medium" => Ok(TimeRange::MediumTerm),
+        "long" => Ok(TimeRange::LongTerm),
+        _ => Err("Not a valid time range".to_string()),
+    }
+}
+
+/// Switch to the playback device requested using the device argument, if any
+pub async fn switch_device(player: &mut SpotifyPlayer, matches: &ArgMatches) -> Result<()> {
+    if let Some(filter) = matches.get_one::<String>("device") {
+        let devices = player.devices().await?;
+
+        match find_device(devices, filter)? {
+            Some(d) => player.set_device(d, false).await?,
+            None => return Err(anyhow!("No playback device matching \"{}\" found", filter)),
+        }
+    }
+
+    Ok(())
+}
+
+/// Print the status line of the bar command, or the placeholder if nothing is playing
+fn print_bar(bar: &ArgMatches, status: Option<Status>) {
+    let line = match status {
+        Some(status) => format::render(
+            bar.get_one::<String>("format")
+                .map(|f| f.as_str())
+                .unwrap_or(DEFAULT_BAR_FORMAT),
+            &status,
+        ),
+        None => bar
+            .get_one::<String>("placeholder")
+            .cloned()
+            .unwrap_or_default(),
+    };
+
+    // Status bars expect exactly one line
+    println!("{}", line.replace(['\r', '\n'], " "));
+}
+
+/// Keep printing the current track on a single line until the user presses Ctrl-C
+///
+/// If an output file is specified, the file is kept updated instead. If JSON lines are requested,
+/// every change is printed on its own line instead
+async fn follow_current(
+    player: &mut SpotifyPlayer,
+    current: &ArgMatches,
+    interval: Duration,
+) -> Result<()> {
+    if let Some(path) = current.get_one::<PathBuf>("output") {
+        return tokio::select! {
+            result = follow_file(player, current, path, interval) => result,
+            _ = tokio::signal::ctrl_c() => Ok(()),
+        };
+    }
+
+    if current.get_flag("json-lines") {
+        return tokio::select! {
+            result = follow_json_lines(player, interval) => result,
+            _ = tokio::signal::ctrl_c() => Ok(()),
+        };
+    }
+
+    // Hide the cursor, so it doesn't flicker at the end of the line
+    print!("\x1b[?25l");
+
+    let result = tokio::select! {
+        result = follow_loop(player, current, interval) => result,
+        _ = tokio::signal::ctrl_c() => Ok(()),
+    };
+
+    // Always restore the cursor and end the line, even if following failed
+    println!("\x1b[?25h");
+
+    result
+}
+
+/// Rewrite the current line with the playback status, whenever the interval elapses
+///
+/// Errors are displayed in place of the status, so a single failing request doesn't stop following
+async fn follow_loop(
+    player: &mut SpotifyPlayer,
+    current: &ArgMatches,
+    interval: Duration,
+) -> Result<()> {
+    loop {
+        let line = match player.status().await {
+            Ok(status) => current_line(current, status.as_ref(), true)?,
+            Err(e) => format!("Error: {}", e),
+        };
+
+        // Lines wrapping around can't be rewritten, so they are cut to the terminal width
+        let columns = terminal::size()
+            .map(|(columns, _)| columns as usize)
+            .unwrap_or(80);
+        let line: String = line.chars().take(columns.saturating_sub(1)).collect();
+
+        print!("\r\x1b[2K{}", line);
+        std::io::stdout()
+            .flush()
+            .context("Failed writing the current track")?;
+
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Rewrite the output file with the playback status, whenever the interval elapses and it changed
+///
+/// Errors are printed to stderr and leave the file untouched, so readers keep the last status
+async fn follow_file(
+    player: &mut SpotifyPlayer,
+    current: &ArgMatches,
+    path: &Path,
+    interval: Duration,
+) -> Result<()> {
+    let mut written: Option<String> = None;
+
+    loop {
+        match player.status().await {
+            Ok(status) => {
+                let line = current_line(current, status.as_ref(), true)?;
+
+                if written.as_ref() != Some(&line) {
+                    output::write_atomic(path, &format!("{}\n", line))?;
+                    written = Some(line);
+                }
+            }
+            Err(e) => eprintln!("Error: {}", e),
+        }
+
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Print the playback status as a JSON object on its own line, whenever the interval elapses and
+/// it changed
+///
+/// Errors are printed to stderr, so a single failing request doesn't stop following
+async fn follow_json_lines(player: &mut SpotifyPlayer, interval: Duration) -> Result<()> {
+    let mut printed: Option<String> = None;
+
+    loop {
+        match player.status().await {
+            Ok(status) => {
+                let status = status.as_ref().map(StatusSummary::from);
+
+                // The timestamp always changes, so only the status is compared
+                let status_str = serde_json::to_string(&status)
+                    .context("Failed serializing the playback status")?;
+
+                if printed.as_ref() != Some(&status_str) {
+                    let event = StatusEvent {
+                        timestamp: chrono::Utc::now()
+                            .to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+                        status,
+                    };
+
+                    // Consumers reading from a pipe should see every change right away
+                    let mut stdout = std::io::stdout().lock();
+                    writeln!(
+                        stdout,
+                        "{}",
+                        serde_json::to_string(&event)
+                            .context("Failed serializing the playback status")?
+                    )
+                    .and_then(|()| stdout.flush())
+                    .context("Failed writing the playback status")?;
+
+                    printed = Some(status_str);
+                }
+            }
+            Err(e) => eprintln!("Error: {}", e),
+        }
+
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Get the line output by the current command for the playback status
+///
+/// Uses the format argument if specified, otherwise the default format depends on whether the
+/// status is followed
+fn current_line(current: &ArgMatches, status: Option<&Status>, follow: bool) -> Result<String> {
+    if current.get_flag("json") {
+        return serde_json::to_string(&status.map(StatusSummary::from))
+            .context("Failed serializing the playback status");
+    }
+
+    let line = match status {
+        Some(status) => format::render(
+            current
+                .get_one::<String>("format")
+                .map(|f| f.as_str())
+                .unwrap_or(match (follow, current.get_flag("remaining")) {
+                    (true, false) => FOLLOW_FORMAT,
+                    (true, true) => REMAINING_FOLLOW_FORMAT,
+                    (false, false) => DEFAULT_CURRENT_FORMAT,
+                    (false, true) => REMAINING_CURRENT_FORMAT,
+                }),
+            status,
+        ),
+        None => "Nothing playing".to_string(),
+    };
+
+    // The output is always a single line
+    Ok(line.replace(['\r', '\n'], " "))
+}
+
+/// Queue the items selected from the search results, playing the first one if requested
+///
+/// Only tracks and episodes can be queued, other items are skipped
+async fn queue_selected(
+    player: &mut SpotifyPlayer,
+    selected: Vec<Box<dyn Playable>>,
+    search: &ArgMatches,
+) -> Result<()> {
+    let mut items = selected.iter().map(|s| s.as_ref());
+
+    if search.get_flag("play") {
+        if let Some(first) = items.next() {
+            player.play(first, None).await?;
+        }
+    }
+
+    let (queueable, skipped): (Vec<&dyn Playable>, Vec<&dyn Playable>) =
+        items.partition(|item| item.playable_id().is_some());
+
+    for item in &queueable {
+        player.add_to_queue(*item).await?;
+    }
+
+    if !queueable.is_empty() {
+        println!("Queued {} items", queueable.len());
+    }
+
+    if !skipped.is_empty() {
+        println!(
+            "Skipped {} items, only tracks and episodes can be queued",
+            skipped.len()
+        );
+    }
+
+    Ok(())
+}
+
+/// When and how autoplay queues recommendations
+struct AutoplaySettings {
+    /// Recommendations are queued once fewer items than this are coming up
+    threshold: u32,
+    /// How many recently played tracks recommendations are based on
+    seeds: u32,
+    /// How many recommended tracks are queued at once
+    count: u32,
+}
+
+/// Queue recommendations whenever the queue runs low, checking it whenever the interval elapses
+///
+/// Errors are printed to stderr, so a single failing request doesn't stop autoplay
+async fn autoplay_loop(
+    player: &mut SpotifyPlayer,
+    settings: &AutoplaySettings,
+    interval: Duration,
+) -> Result<()> {
+    // Recommendations often repeat, so every track is only queued once
+    let mut queued: HashSet<String> = HashSet::new();
+
+    loop {
+        match queue_recommendations(player, settings, &mut queued).await {
+            Ok(0) => {}
+            Ok(n) => println!("Queued {} recommended tracks", n),
+            Err(e) => eprintln!("Error: {:#}", e),
+        }
+
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Queue recommendations if the queue runs low, skipping tracks which were already queued
+///
+/// Returns the amount of queued tracks
+async fn queue_recommendations(
+    player: &mut SpotifyPlayer,
+    settings: &AutoplaySettings,
+    queued: &mut HashSet<String>,
+) -> Result<usize> {
+    if player.queue_length().await? >= settings.threshold as usize {
+        return Ok(0);
+    }
+
+    let seeds = player.recent_track_ids(settings.seeds).await?;
+
+    if seeds.is_empty() {
+        return Err(anyhow!(
+            "No recently played tracks to base recommendations on"
+        ));
+    }
+
+    let mut count = 0;
+
+    for track in player.recommendations(seeds, settings.count).await? {
+        let uri = match track.playable_id() {
+            Some(id) => id.uri(),
+            None => continue,
+        };
+
+        if queued.insert(uri) {
+            player.add_to_queue(&track).await?;
+            count += 1;
+        }
+    }
+
+    Ok(count)
+}
+
+/// Get the playback status for the bar command, without ever prompting the user
+///
+/// Returns Ok(None) if the user is unauthorized or nothing is playing
+async fn bar_status(matches: &ArgMatches) -> Result<Option<Status>> {
+    match unprompted_player(matches).await? {
+        Some(mut player) => player.status().await,
+        None => Ok(None),
+    }
+}
+
+/// Print the current track as a compact line, using only a single request
+///
+/// Prints an empty line if nothing is playing, so status bars polling it are simply cleared
+async fn print_now(player: &mut SpotifyPlayer) -> Result<()> {
+    let line = match player.now_playing().await? {
+        Some(track) => format!("{} - {}", track.title, track.by.join(", ")),
+        None => String::new(),
+    };
+
+    println!("{}", line.replace(['\r', '\n'], " "));
+
+    Ok(())
+}
+
+/// Get a player using the cached tokens, without ever prompting the user
+///
+/// Returns Ok(None) if the user is unauthorized
+async fn unprompted_player(matches: &ArgMatches) -> Result<Option<SpotifyPlayer>> {
+    let config = config::load()?;
+    config.proxy()?;
+
+    let mut player = match auth::load_cached(false).await? {
+        Some(player) => player,
+        None => return Ok(None),
+    };
+
+    player.set_max_retries(config.max_retries());
+
+    if let Some(timeout) = timeout_matches(matches, &config)? {
+        player.set_request_timeout(timeout);
+    }
+
+    Ok(Some(player))
+}
+
+/// Search for content and let the user select a single item
+///
+/// Returns Ok(None) if nothing matches the search
+async fn search_single(
+    player: &mut SpotifyPlayer,
+    matches: &ArgMatches,
+    config: &Config,
+) -> Result<Option<Box<dyn Playable>>> {
+    let query = match matches.get_one::<String>("content") {
+        Some(query) => query,
+        None => return Ok(None),
+    };
+
+    let res = player
+        .search(
+            query.clone(),
+            type_matches(matches),
+            config.market()?,
+            Some(10),
+            None,
+            SearchFilter::default(),
+        )
+        .await?;
+
+    if res.is_empty() {
+        println!("No matches found");
+        return Ok(None);
+    }
+
+    Ok(Some(ui::select_playable(res)?))
+}
+
+/// Ask the user to confirm a destructive action
+///
+/// Confirmation is skipped if --yes is specified or if there is no terminal to ask in
+fn confirmed(matches: &ArgMatches, prompt: &str) -> Result<bool> {
+    if matches.get_flag("yes") || !std::io::stdin().is_terminal() {
+        return Ok(true);
+    }
+
+    ui::confirm(prompt)
+}
+
+/// Print playable items as a JSON array of their summaries
+fn print_summaries(playables: &[Box<dyn Playable>]) -> Result<()> {
+    let summaries: Vec<PlayableSummary> = playables.iter().map(|p| p.to_summary()).collect();
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&summaries).context("Failed serializing results")?
+    );
+
+    Ok(())
+}
+
+/// Print items as a Markdown list linking to each item, e.g. for notes
+fn print_markdown(playables: &[Box<dyn Playable>]) {
+    for playable in playables {
+        let summary = playable.to_summary();
+
+        let title = match summary.artists.is_empty() {
+            true => summary.name,
+            false => format!("{} by {}", summary.name, summary.artists.join(", ")),
+        };
+
+        // Brackets in names would end the link text early
+        let title = title.replace('[', "\\[").replace(']', "\\]");
+
+        match playable.url() {
+            Some(url) => println!("- [{}]({})", title, url),
+            None => println!("- {}", title),
+        }
+    }
+}
+
+/// Let the user drill into a selected item, e.g. to pick a single track of an album or a single
+/// episode of a show
+///
+/// Returns the item which should be played
+async fn drill_in(
+    player: &mut SpotifyPlayer,
+    selected: Box<dyn Playable>,
+) -> Result<Box<dyn Playable>> {
+    if let Some(album_id) = selected.album_id() {
+        if let ui::AlbumAction::ShowTracks = ui::select_album_action()? {
+            return ui::select_playable(player.album_tracks(album_id).await?);
+        }
+    }
+
+    if let Some(show_id) = selected.show_id() {
+        if let ui::ShowAction::ShowEpisodes = ui::select_show_action()? {
+            let episodes = player.show_episodes(show_id).await?;
+
+            if episodes.is_empty() {
+                return Err(anyhow!("The show doesn't have any episodes"));
+            }
+
+            return ui::select_playable(episodes);
+        }
+    }
+
+    Ok(selected)
+}
+
+/// Find the playback device with the ID or best matching the name filter
+///
+/// The user is asked to select one, if several devices match about as well
+fn find_device(devices: Vec<Device>, filter: &str) -> Result<Option<Device>> {
+    // IDs are unambiguous, unlike names, which several devices may match
+    if let Some(device) = devices.iter().find(|d| d.id.as_deref() == Some(filter)) {
+        return Ok(Some(device.clone()));
+    }
+
+    pick_match(
+        fuzzy::find(devices, filter, |d| d.name.clone()),
+        ui::select_device,
+    )
+}
+
+/// Get the item of a fuzzy match, letting the user select one if several items match about as
+/// well
+///
+/// Without a terminal to ask in, the best matching item is picked
+fn pick_match<T>(
+    found: fuzzy::Match<T>,
+    select: impl FnOnce(Vec<T>) -> Result<T>,
+) -> Result<Option<T>> {
+    match found {
+        fuzzy::Match::Best(item) => Ok(Some(item)),
+        fuzzy::Match::Candidates(candidates) if std::io::stdin().is_terminal() => {
+            select(candidates).map(Some)
+        }
+        fuzzy::Match::Candidates(candidates) => Ok(candidates.into_iter().next()),
+        fuzzy::Match::None => Ok(None),
+    }
+}
+
+/// Get all selected SearchTypes from argument matches, defaulting to tracks if none are selected
+fn type_matches(matches: &ArgMatches) -> Vec<SearchType> {
+    let search_types: Vec<SearchType> = [
+        ("track", SearchType::Track),
+        ("playlist", SearchType::Playlist),
+        ("album", SearchType::Album),
+        ("artist", SearchType::Artist),
+        ("show", SearchType::Show),
+        ("episode", SearchType::Episode),
+    ]
+    .into_iter()
+    .filter(|(flag, _)| matches!(matches.try_get_one::<bool>(flag), Ok(Some(true))))
+    .map(|(_, search_type)| search_type)
+    .collect();
+
+    match search_types.is_empty() {
+        true => vec![SearchType::Track],
+        false => search_types,
+    }
+}
+
+/// Get the poll interval from argument matches, falling back to the configured poll interval
+fn poll_interval(matches: &ArgMatches, config: &Config) -> Duration {
+    match matches.get_one::<f64>("poll-interval") {
+        Some(seconds) => config::clamp_poll_interval(*seconds),
+        None => config.poll_interval(),
+    }
+}
+
+/// Get the request timeout from argument matches, falling back to the configured timeout
+fn timeout_matches(matches: &ArgMatches, config: &Config) -> Result<Option<Duration>> {
+    match matches.get_one::<Duration>("timeout") {
+        Some(timeout) => Ok(Some(*timeout)),
+        None => config.timeout(),
+    }
+}
+
+/// Get the Market from argument matches, falling back to the configured default market
+fn market_matches(matches: &ArgMatches, config: &Config) -> Result<Option<Market>> {
+    match matches.get_one::<Market>("market") {
+        Some(market) => Ok(Some(*market)),
+        None => config.market(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_uris_to_links() {
+        assert_eq!(
+            uri_parser("spotify:track:4PTG3Z6ehGkBFwjybzWkR8"),
+            Ok("https://open.spotify.com/track/4PTG3Z6ehGkBFwjybzWkR8".to_string())
+        );
+    }
+
+    #[test]
+    fn keeps_links() {
+        let link = "https://open.spotify.com/album/6N9PS4QXF1D0OWPk0Sxtb4";
+        assert_eq!(uri_parser(link), Ok(link.to_string()));
+    }
+
+    #[test]
+    fn rejects_other_links() {
+        assert!(uri_parser("https://anything.example").is_err());
+        assert!(
+            uri_parser("https://open.spotify.com.example/track/4PTG3Z6ehGkBFwjybzWkR8").is_err()
+        );
+        assert!(uri_parser("https://example.com/?r=open.spotify.com/track/x").is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_uris() {
+        assert!(uri_parser("spotify:").is_err());
+        assert!(uri_parser("track:4PTG3Z6ehGkBFwjybzWkR8").is_err());
+        assert!(uri_parser("http://open.spotify.com/track/4PTG3Z6ehGkBFwjybzWkR8").is_err());
+    }
+
+    #[test]
+    fn keeps_spotify_uris() {
+        assert_eq!(
+            spotify_uri("spotify:album:6N9PS4QXF1D0OWPk0Sxtb4"),
+            Some("spotify:album:6N9PS4QXF1D0OWPk0Sxtb4".to_string())
+        );
+    }
+
+    #[test]
+    fn converts_shared_links_to_uris() {
+        assert_eq!(
+            spotify_uri("https://open.spotify.com/track/4PTG3Z6ehGkBFwjybzWkR8?si=abc123"),
+            Some("spotify:track:4PTG3Z6ehGkBFwjybzWkR8".to_string())
+        );
+        assert_eq!(
+            spotify_uri("https://open.spotify.com/intl-de/playlist/37i9dQZF1DXcBWIGoYBM5M#top"),
+            Some("spotify:playlist:37i9dQZF1DXcBWIGoYBM5M".to_string())
+        );
+    }
+
+    #[test]
+    fn ignores_other_text() {
+        assert_eq!(spotify_uri("never gonna give you up"), None);
+        assert_eq!(
+            spotify_uri("https://example.com/track/4PTG3Z6ehGkBFwjybzWkR8"),
+            None
+        );
+        assert_eq!(spotify_uri("https://open.spotify.com/"), None);
+        assert_eq!(spotify_uri("https://open.spotify.com/track"), None);
+        assert_eq!(spotify_uri("https://open.spotify.com/track/"), None);
+        assert_eq!(spotify_uri("spotify:"), None);
+        assert_eq!(
+            spotify_uri("https://evil.example/?r=open.spotify.com/track/4PTG3Z6ehGkBFwjybzWkR8"),
+            None
+        );
+        assert_eq!(
+            spotify_uri("https://open.spotify.com.example/track/4PTG3Z6ehGkBFwjybzWkR8"),
+            None
+        );
     }
 }
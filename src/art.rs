@@ -0,0 +1,271 @@
+use std::{collections::HashMap, io::IsTerminal};
+
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use image::{imageops::FilterType, RgbImage};
+use ratatui::crossterm::terminal;
+use rspotify::model::Image;
+
+/// The maximum width of the rendered cover art in terminal columns
+const MAX_ART_WIDTH: u32 = 32;
+
+/// The width in pixels images are scaled to for sixel output
+const SIXEL_WIDTH: u32 = 256;
+
+/// The size of the chunks the kitty graphics protocol payload is split into
+const KITTY_CHUNK_SIZE: usize = 4096;
+
+/// How the cover art is drawn in the terminal
+#[derive(Clone, Copy)]
+pub enum Backend {
+    /// Colored unicode half blocks, supported by every true color terminal
+    Ascii,
+    /// Sixel graphics
+    Sixel,
+    /// The kitty graphics protocol
+    Kitty,
+}
+
+impl Backend {
+    /// Detect the best backend supported by the terminal
+    ///
+    /// Querying the terminal is unreliable, so this relies on the environment variables
+    /// terminals are known to set
+    pub fn detect() -> Self {
+        let term = std::env::var("TERM").unwrap_or_default();
+        let term_program = std::env::var("TERM_PROGRAM").unwrap_or_default();
+
+        if std::env::var_os("KITTY_WINDOW_ID").is_some()
+            || term.contains("kitty")
+            || term.contains("ghostty")
+            || matches!(term_program.as_str(), "WezTerm" | "ghostty")
+        {
+            return Backend::Kitty;
+        }
+
+        if term.contains("sixel")
+            || term.starts_with("foot")
+            || term.starts_with("mlterm")
+            || term_program == "iTerm.app"
+        {
+            return Backend::Sixel;
+        }
+
+        Backend::Ascii
+    }
+}
+
+/// Download the cover art and print it, scaled to the terminal width
+///
+/// Skips with a message if there is no cover art or if stdout is not a terminal. The backend is
+/// detected, if none is specified
+pub async fn print_cover(images: &[Image], backend: Option<Backend>) -> Result<()> {
+    if !std::io::stdout().is_terminal() {
+        eprintln!("Cover art can only be displayed in a terminal");
+        return Ok(());
+    }
+
+    let backend = backend.unwrap_or_else(Backend::detect);
+
+    // Block characters can't show much detail, so the smallest image is enough for them
+    let image = match backend {
+        Backend::Ascii => smallest_image(images),
+        Backend::Sixel | Backend::Kitty => detailed_image(images),
+    };
+
+    let image = match image {
+        Some(image) => image,
+        None => {
+            eprintln!("No cover art available");
+            return Ok(());
+        }
+    };
+
+    let cover = download(&image.url).await?;
+
+    // Fall back to the maximum width, if the terminal size can't be determined
+    let columns = terminal::size()
+        .map(|(columns, _)| columns as u32)
+        .unwrap_or(MAX_ART_WIDTH)
+        .min(MAX_ART_WIDTH);
+
+    let output = match backend {
+        Backend::Ascii => render_blocks(&cover, columns),
+        Backend::Sixel => render_sixel(&cover),
+        Backend::Kitty => render_kitty(&cover, columns),
+    };
+
+    print!("{}", output);
+
+    Ok(())
+}
+
+/// Get the smallest image, images with unknown size are considered the largest
+fn smallest_image(images: &[Image]) -> Option<&Image> {
+    images
+        .iter()
+        .min_by_key(|image| image.width.unwrap_or(u32::MAX))
+}
+
+/// Get the smallest image which is still detailed enough for pixel graphics
+fn detailed_image(images: &[Image]) -> Option<&Image> {
+    images
+        .iter()
+        .filter(|image| image.width.unwrap_or(u32::MAX) >= SIXEL_WIDTH)
+        .min_by_key(|image| image.width.unwrap_or(u32::MAX))
+        .or_else(|| images.iter().max_by_key(|image| image.width.unwrap_or(0)))
+}
+
+/// Download an image and decode it
+async fn download(url: &str) -> Result<RgbImage> {
+    let bytes = reqwest::get(url)
+        .await
+        .and_then(|response| response.error_for_status())
+        .context("Failed downloading cover art")?
+        .bytes()
+        .await
+        .context("Failed downloading cover art")?;
+
+    let image = image::load_from_memory(&bytes).context("Failed decoding cover art")?;
+
+    Ok(image.to_rgb8())
+}
+
+/// Get the amount of terminal rows an image spans, when scaled to the given columns
+///
+/// Terminal cells are about twice as high as wide, so every row covers two pixel rows
+fn rows(image: &RgbImage, columns: u32) -> u32 {
+    (columns * image.height() / image.width().max(1) / 2).max(1)
+}
+
+/// Render an image as rows of half blocks, each character cell displaying two pixels
+fn render_blocks(image: &RgbImage, columns: u32) -> String {
+    let height = rows(image, columns) * 2;
+    let scaled = image::imageops::resize(image, columns, height, FilterType::Triangle);
+
+    let mut output = String::new();
+
+    for y in (0..height).step_by(2) {
+        for x in 0..columns {
+            let top = scaled.get_pixel(x, y);
+            let bottom = scaled.get_pixel(x, y + 1);
+
+            // Upper half block, the foreground colors the top and the background the bottom pixel
+            output.push_str(&format!(
+                "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m▀",
+                top[0], top[1], top[2], bottom[0], bottom[1], bottom[2]
+            ));
+        }
+
+        output.push_str("\x1b[0m\n");
+    }
+
+    output
+}
+
+/// Render an image using the kitty graphics protocol, scaled by the terminal to the columns
+fn render_kitty(image: &RgbImage, columns: u32) -> String {
+    let payload = STANDARD.encode(image.as_raw());
+    let chunks: Vec<&[u8]> = payload.as_bytes().chunks(KITTY_CHUNK_SIZE).collect();
+
+    let mut output = String::new();
+
+    for (i, chunk) in chunks.iter().enumerate() {
+        // Every chunk but the last one signals that more data follows
+        let more = (i + 1 < chunks.len()) as u8;
+        // Base64 only consists of ASCII characters
+        let chunk = std::str::from_utf8(chunk).unwrap_or_default();
+
+        // Only the first chunk carries the image parameters: transmit and display raw RGB data
+        match i {
+            0 => output.push_str(&format!(
+                "\x1b_Ga=T,f=24,s={},v={},c={},r={},m={};{}\x1b\\",
+                image.width(),
+                image.height(),
+                columns,
+                rows(image, columns),
+                more,
+                chunk
+            )),
+            _ => output.push_str(&format!("\x1b_Gm={};{}\x1b\\", more, chunk)),
+        }
+    }
+
+    output.push('\n');
+    output
+}
+
+/// Render an image as sixel graphics
+///
+/// Colors are reduced to a 6x6x6 color cube, which fits into the 256 color registers most sixel
+/// terminals support
+fn render_sixel(image: &RgbImage) -> String {
+    let height = (SIXEL_WIDTH * image.height() / image.width().max(1)).max(1);
+    let scaled = image::imageops::resize(image, SIXEL_WIDTH, height, FilterType::Triangle);
+
+    // Map every pixel to the index of its color in the color cube
+    let quantize = |c: u8| (c as u32 * 5 + 127) / 255;
+    let indices: Vec<u32> = scaled
+        .pixels()
+        .map(|p| quantize(p[0]) * 36 + quantize(p[1]) * 6 + quantize(p[2]))
+        .collect();
+
+    // Enter sixel mode and define the color registers, with channels in percent
+    let mut output = String::from("\x1bPq");
+
+    for index in 0..216 {
+        let percent = |level: u32| level * 100 / 5;
+        output.push_str(&format!(
+            "#{};2;{};{};{}",
+            index,
+            percent(index / 36),
+            percent(index / 6 % 6),
+            percent(index % 6)
+        ));
+    }
+
+    // Every sixel character covers a column of six pixels, so the image is drawn in bands
+    for band in (0..height).step_by(6) {
+        // Collect the pixel columns of every color used in this band as sixel bit masks
+        let mut colors: HashMap<u32, Vec<u8>> = HashMap::new();
+
+        for dy in 0..6.min(height - band) {
+            for x in 0..SIXEL_WIDTH {
+                let index = indices[((band + dy) * SIXEL_WIDTH + x) as usize];
+                let masks = colors
+                    .entry(index)
+                    .or_insert_with(|| vec![0; SIXEL_WIDTH as usize]);
+                masks[x as usize] |= 1 << dy;
+            }
+        }
+
+        // Draw every color on top of each other, returning to the start of the band in between
+        for (index, masks) in colors {
+            output.push_str(&format!("#{}", index));
+            push_sixel_runs(&mut output, &masks);
+            output.push('$');
+        }
+
+        output.push('-');
+    }
+
+    output.push_str("\x1b\\\n");
+    output
+}
+
+/// Push sixel bit masks as characters, compressing repeated characters
+fn push_sixel_runs(output: &mut String, masks: &[u8]) {
+    let mut i = 0;
+
+    while i < masks.len() {
+        let run = masks[i..].iter().take_while(|&&m| m == masks[i]).count();
+        let char = (masks[i] + 63) as char;
+
+        match run {
+            1..=3 => output.extend(std::iter::repeat_n(char, run)),
+            _ => output.push_str(&format!("!{}{}", run, char)),
+        }
+
+        i += run;
+    }
+}
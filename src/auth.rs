@@ -1,22 +1,63 @@
-use anyhow::{Context, Result};
-use const_format::concatcp;
+use anyhow::{anyhow, Context, Result};
 use core::str;
 use rspotify::{
+    http::HttpError,
+    model::ModelError,
     prelude::{BaseClient, OAuthClient},
-    scopes, AuthCodePkceSpotify, Config, Credentials, OAuth,
+    scopes, AuthCodePkceSpotify, ClientError, Config, Credentials, OAuth, Token,
 };
 use serde::{Deserialize, Serialize};
-use std::{collections::HashSet, fs, path::PathBuf};
+use std::{
+    collections::HashSet,
+    fs,
+    io::ErrorKind,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    path::PathBuf,
+    time::Duration,
+};
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
-    net::TcpListener,
+    net::{TcpListener, TcpStream},
 };
 
 use crate::{client::SpotifyPlayer, ui};
 
-const CALLBACK_SERVER_PORT: u32 = 8080;
-const CALLBACK_URI: &'static str =
-    concatcp!("http://localhost:", CALLBACK_SERVER_PORT, "/callback");
+const DEFAULT_CALLBACK_SERVER_PORT: u16 = 8080;
+pub const DEFAULT_CALLBACK_TIMEOUT: Duration = Duration::from_secs(120);
+const CALLBACK_SERVER_BIND_ATTEMPTS: u32 = 5;
+const CALLBACK_SERVER_BIND_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Settings of the authorization flow
+pub struct FlowOptions {
+    /// The port the callback server listens on, see callback_port() if none is given
+    pub callback_port: Option<u16>,
+    /// Whether the authorization link is opened in a browser, or only printed
+    pub open_browser: bool,
+    /// How the callback URL is collected after authorizing
+    pub callback_mode: CallbackMode,
+    /// How long the callback server waits for the callback, before asking the user for the URL
+    pub callback_timeout: Duration,
+}
+
+impl FlowOptions {
+    /// Get the port the callback server listens on
+    ///
+    /// Only resolved when authorizing, so an invalid port doesn't affect other commands
+    fn port(&self) -> Result<u16> {
+        match self.callback_port {
+            Some(port) => Ok(port),
+            None => callback_port(),
+        }
+    }
+}
+
+/// How the callback URL spotify redirects to after authorizing is collected
+pub enum CallbackMode {
+    /// Receive the callback using a local server, falling back to asking the user for the URL
+    Server,
+    /// Ask the user for the URL, without running a local server
+    Manual,
+}
 
 #[derive(Serialize, Deserialize)]
 pub struct ClientCredentials {
@@ -24,7 +65,13 @@ pub struct ClientCredentials {
 }
 
 /// Get the directory where data should be stored
+///
+/// Can be overridden using the SPOTIC_DATA_DIR environment variable
 pub fn data_dir() -> PathBuf {
+    if let Some(data_dir) = std::env::var_os("SPOTIC_DATA_DIR") {
+        return PathBuf::from(data_dir);
+    }
+
     // Store in conventional place or next to binary
     let mut data_dir = dirs::data_dir().unwrap_or(PathBuf::from("./"));
     data_dir.push("spotic");
@@ -32,14 +79,26 @@ pub fn data_dir() -> PathBuf {
 }
 
 /// Get the tokens storage path
+///
+/// Can be overridden using the SPOTIC_TOKENS_PATH environment variable
 pub fn tokens_path() -> PathBuf {
+    if let Some(tokens_path) = std::env::var_os("SPOTIC_TOKENS_PATH") {
+        return PathBuf::from(tokens_path);
+    }
+
     let mut credentials_path = data_dir();
     credentials_path.push("tokens.json");
     credentials_path
 }
 
 /// Get the client credentials storage path
+///
+/// Can be overridden using the SPOTIC_CREDENTIALS_PATH environment variable
 pub fn credentials_path() -> PathBuf {
+    if let Some(credentials_path) = std::env::var_os("SPOTIC_CREDENTIALS_PATH") {
+        return PathBuf::from(credentials_path);
+    }
+
     let mut client_path = data_dir();
     client_path.push("credentials.json");
     client_path
@@ -47,7 +106,19 @@ pub fn credentials_path() -> PathBuf {
 
 /// Ensure the data directory is created
 pub fn ensure_dir() -> Result<()> {
-    fs::create_dir_all(data_dir()).context("Failed creating data directory")
+    fs::create_dir_all(data_dir()).context("Failed creating data directory")?;
+
+    // Overridden paths may point outside of the data directory
+    for path in [tokens_path(), credentials_path()] {
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent).context("Failed creating data directory")?;
+        }
+    }
+
+    Ok(())
 }
 
 /// Do saved tokens and credentials exist
@@ -55,6 +126,16 @@ pub fn saved() -> bool {
     tokens_path().exists() && credentials_path().exists()
 }
 
+/// Delete the cached tokens, so the next run has to authorize again
+pub fn clear_tokens() -> Result<()> {
+    match fs::remove_file(tokens_path()) {
+        Err(e) if e.kind() != ErrorKind::NotFound => {
+            Err(e).context("Failed deleting the cached tokens")
+        }
+        _ => Ok(()),
+    }
+}
+
 /// Get the scopes required for all functionality
 ///
 /// In case these get updated and are not granted by the current authorization, the user will
@@ -64,14 +145,46 @@ fn scopes() -> HashSet<String> {
         "user-read-currently-playing",
         "user-modify-playback-state",
         "playlist-read-private",
-        "user-read-playback-state"
+        "user-read-playback-state",
+        "user-read-recently-played",
+        "user-top-read",
+        "user-follow-modify",
+        "playlist-modify-public",
+        "playlist-modify-private",
+        "user-read-private",
+        "user-read-playback-position",
+        "user-library-read"
     )
 }
 
+/// Get the port the callback server listens on
+///
+/// Defaults to 8080, can be overridden using the SPOTIC_CALLBACK_PORT environment variable
+pub fn callback_port() -> Result<u16> {
+    match std::env::var("SPOTIC_CALLBACK_PORT") {
+        Ok(port) => port
+            .parse::<u16>()
+            .with_context(|| format!("\"{}\" is not a valid callback port", port)),
+        Err(_) => Ok(DEFAULT_CALLBACK_SERVER_PORT),
+    }
+}
+
+/// Is spotic running in a remote session, e.g. over SSH
+///
+/// A browser opened in a remote session would open on the remote machine, if at all
+pub fn remote_session() -> bool {
+    std::env::var_os("SSH_CONNECTION").is_some() || std::env::var_os("SSH_TTY").is_some()
+}
+
+/// Get the callback URI the spotify API redirects to after authorizing
+fn callback_uri(port: u16) -> String {
+    format!("http://localhost:{}/callback", port)
+}
+
 /// Get the oauth settings used across the authorization code
-fn oauth() -> OAuth {
+fn oauth(callback_port: u16) -> OAuth {
     OAuth {
-        redirect_uri: CALLBACK_URI.to_string(),
+        redirect_uri: callback_uri(callback_port),
         scopes: scopes(),
         ..Default::default()
     }
@@ -89,14 +202,15 @@ fn config() -> Config {
 
 /// Try to load authorization tokens from cache
 ///
-/// Returns Ok(None) in case the scope does not match with the clients or we don't have any tokens
-/// cached
-/// - Or token caching is disabled (it is not)
+/// Returns Ok(None) in case the token lacks scopes required by the client or we don't have any
+/// tokens cached
 /// - Or token is expired (we still load it, so we can refresh)
-/// So basically, every time we need to re-authorize we return Ok(None)
+/// - Or refreshing the token was rejected, because it expired or was revoked
+///   So basically, every time we need to re-authorize we return Ok(None)
 ///
-/// Returns an Err() in case tokens are cached, but can't be loaded
-pub async fn load_cached() -> Result<Option<SpotifyPlayer>> {
+/// Returns an Err() in case tokens are cached, but can't be loaded. If prompt is set, the user is
+/// asked whether to delete the unreadable cache and re-authorize instead
+pub async fn load_cached(prompt: bool) -> Result<Option<SpotifyPlayer>> {
     if !saved() {
         return Ok(None);
     }
@@ -109,25 +223,80 @@ pub async fn load_cached() -> Result<Option<SpotifyPlayer>> {
 
     let spotify = AuthCodePkceSpotify::with_config(
         Credentials::new_pkce(&creds.client_id),
-        oauth(),
+        // Refreshing tokens doesn't use the redirect URI, so an invalid port is only reported
+        // when authorizing
+        oauth(callback_port().unwrap_or(DEFAULT_CALLBACK_SERVER_PORT)),
         config(),
     );
 
-    match spotify.read_token_cache(true).await {
-        Ok(Some(token)) => {
+    match Token::from_cache(tokens_path()) {
+        Ok(token) => {
+            // Features added since authorizing may need scopes the cached token wasn't granted
+            let missing = missing_scopes(&token);
+            if !missing.is_empty() {
+                eprintln!(
+                    "Spotic requires additional permissions ({}), please authorize again\n",
+                    missing.join(", ")
+                );
+                return Ok(None);
+            }
+
             *spotify.token.lock().await.unwrap() = Some(token.clone());
 
             if token.is_expired() {
-                spotify
-                    .refresh_token()
-                    .await
-                    .context("Failed to refresh token")?;
+                match spotify.refresh_token().await {
+                    Ok(()) => {}
+                    // The refresh token expired or was revoked, so only authorizing again helps
+                    Err(e) if is_rejected_refresh(&e) => {
+                        eprintln!(
+                            "Your authorization expired or was revoked, please authorize again\n"
+                        );
+                        return Ok(None);
+                    }
+                    Err(e) => {
+                        return Err(e).context(
+                            "Failed to refresh token, check your internet connection and try again",
+                        )
+                    }
+                }
             }
 
             Ok(Some(SpotifyPlayer::new(spotify)))
         }
-        Ok(None) => Ok(None),
-        Err(e) => Err(e).context("Failed reading cached tokens, try re-authorizing"),
+        // The cache was removed in the meantime, which is the same as having no cache
+        Err(ModelError::Io(e)) if e.kind() == ErrorKind::NotFound => Ok(None),
+        // The cache exists, but is corrupt or can't be read
+        Err(e) => {
+            if prompt
+                && ui::confirm("The cached tokens can't be read, delete them and re-authorize?")?
+            {
+                clear_tokens()?;
+                return Ok(None);
+            }
+
+            Err(e).context("Failed reading cached tokens, try re-authorizing")
+        }
+    }
+}
+
+/// Get the required scopes which weren't granted to the token, sorted by name
+fn missing_scopes(token: &Token) -> Vec<String> {
+    let mut missing: Vec<String> = scopes().difference(&token.scopes).cloned().collect();
+    missing.sort();
+    missing
+}
+
+/// Did spotify reject refreshing the token, e.g. with an "invalid_grant" error
+///
+/// This happens when the refresh token expired or the app was removed from the account, as
+/// opposed to transient errors like network failures
+fn is_rejected_refresh(error: &ClientError) -> bool {
+    match error {
+        ClientError::Http(http_error) => match http_error.as_ref() {
+            HttpError::StatusCode(response) => matches!(response.status().as_u16(), 400 | 401),
+            _ => false,
+        },
+        _ => false,
     }
 }
 
@@ -137,10 +306,11 @@ pub async fn load_cached() -> Result<Option<SpotifyPlayer>> {
 /// - Generate the authorization url and open it
 /// - Collect the redirect url, get the code from it
 /// - Write the tokens to the cache file
-pub async fn run_flow() -> Result<SpotifyPlayer> {
-    let creds = ui::collect_creds(CALLBACK_URI).context("Failed collecting credentials")?;
+pub async fn run_flow(flow: &FlowOptions) -> Result<SpotifyPlayer> {
+    let port = flow.port()?;
+    let creds = ui::collect_creds(&callback_uri(port)).context("Failed collecting credentials")?;
 
-    authorize_spotify(creds, oauth()).await
+    authorize_spotify(creds, oauth(port), flow).await
 }
 
 /// Run the authorization process for spotify
@@ -148,11 +318,15 @@ pub async fn run_flow() -> Result<SpotifyPlayer> {
 /// - Write the credentials to file
 /// - Get the authorization URL
 /// - Collect the code from the callback URL
-/// using either small web server or manual user input
+///   using either small web server or manual user input, depending on the callback mode
 /// - Use the code to request authorization tokens
 /// - Write the tokens to file
 /// - Return a usable SpotifyPlayer if everything went well
-async fn authorize_spotify(creds: Credentials, oauth: OAuth) -> Result<SpotifyPlayer> {
+async fn authorize_spotify(
+    creds: Credentials,
+    oauth: OAuth,
+    flow: &FlowOptions,
+) -> Result<SpotifyPlayer> {
     ensure_dir()?;
 
     let mut spotify = AuthCodePkceSpotify::with_config(creds.clone(), oauth, config());
@@ -173,17 +347,20 @@ async fn authorize_spotify(creds: Credentials, oauth: OAuth) -> Result<SpotifyPl
 
     println!("\nAuthorization link: {}\n", url);
 
-    // Try opening the URL using a browser
-    if open::that(url).is_err() {
+    // Try opening the URL using a browser, unless the user opens it themselves
+    if !flow.open_browser {
+        match flow.callback_mode {
+            CallbackMode::Server => println!(
+                "Open the link in a browser. If the browser runs on another machine, forward the callback port first, e.g. using \"ssh -L {0}:localhost:{0}\".\n",
+                flow.port()?
+            ),
+            CallbackMode::Manual => println!("Open the link in a browser.\n"),
+        }
+    } else if open::that(url).is_err() {
         println!("Failed opening the link in a browser, please open it manually.\n");
     }
 
-    // Either get the callback URL using a locally running web server, or, in case of errors,
-    // let the user enter the URL manually
-    let url = match run_callback_server().await {
-        Ok(url) => url,
-        Err(_) => ui::collect_callback_url().context("Failed reading the callback URL")?,
-    };
+    let url = collect_callback(flow).await?;
 
     // Parse the code from the callback URL
     let code = spotify
@@ -207,42 +384,101 @@ async fn authorize_spotify(creds: Credentials, oauth: OAuth) -> Result<SpotifyPl
     Ok(SpotifyPlayer::new(spotify))
 }
 
+/// Collect the callback URL spotify redirected to after authorizing, depending on the mode
+async fn collect_callback(flow: &FlowOptions) -> Result<String> {
+    match flow.callback_mode {
+        // Get the callback URL using a locally running web server, or, in case of errors, let
+        // the user enter the URL manually
+        CallbackMode::Server => match receive_callback(flow).await {
+            Ok(url) => Ok(url),
+            Err(e) => {
+                println!("{:#}\n", e);
+                ui::collect_callback_url().context("Failed reading the callback URL")
+            }
+        },
+        CallbackMode::Manual => {
+            println!("After authorizing, your browser is redirected to a page which fails to load. Copy its URL from the address bar.\n");
+            ui::collect_callback_url().context("Failed reading the callback URL")
+        }
+    }
+}
+
+/// Run the callback server until it receives the callback or the callback timeout elapses
+///
+/// The browser may never be redirected, e.g. if the user closes it without authorizing, so the
+/// server must not wait forever
+async fn receive_callback(flow: &FlowOptions) -> Result<String> {
+    match tokio::time::timeout(flow.callback_timeout, run_callback_server(flow.port()?)).await
+    {
+        Ok(result) => result,
+        Err(_) => Err(anyhow!(
+            "No callback received within {} seconds. If you did authorize, copy the URL your browser was redirected to",
+            flow.callback_timeout.as_secs()
+        )),
+    }
+}
+
 /// Runs a local server which is used as the callback for the spotify API
 ///
 /// This allows us to do two things:
 /// - Collect the response URL and thus the authorization code automatically
 /// - Show the user a neat "You can close this page now" message after authorizing
-/// the spotify app
-async fn run_callback_server() -> Result<String> {
-    // Listen on the callback port
-    let listener = TcpListener::bind(format!("0.0.0.0:{}", CALLBACK_SERVER_PORT))
-        .await
-        .context("Failed running callback server")?;
-
-    // Accept connection
-    let (mut socket, _) = listener
-        .accept()
-        .await
-        .context("Failed accepting connection")?;
-
-    // Create buffer for reading from socket
-    let mut buffer = vec![0; 1024];
-
-    // Read from socket into buffer, store amount of read bytes
-    let n = socket
-        .read(&mut buffer)
+///   the spotify app
+async fn run_callback_server(port: u16) -> Result<String> {
+    // Listen on the callback port, only on the loopback interfaces so the authorization code
+    // can't be delivered from the network. IPv6 is optional, since it may be unavailable
+    let listener = bind_callback_server(Ipv4Addr::LOCALHOST.into(), port).await?;
+    let listener_v6 = bind_callback_server(Ipv6Addr::LOCALHOST.into(), port)
         .await
-        .context("Failed reading bytes from connection to callback server")?;
-
-    // Create string from byte array until the n (the read amount)
-    let request = str::from_utf8(&buffer[..n]).context("Callback URL is malformed")?;
+        .ok();
+
+    // Browsers may send other requests (e.g. for a favicon) before or instead of the callback,
+    // and open speculative connections which never send anything. Each connection is handled
+    // on its own, so those can't hold up the callback
+    let (callback_tx, mut callback_rx) = tokio::sync::mpsc::channel(1);
+
+    loop {
+        // Accept connection, browsers may resolve localhost to either address
+        let accepted = match &listener_v6 {
+            Some(listener_v6) => tokio::select! {
+                url = callback_rx.recv() => return url.context("Callback server stopped")?,
+                res = listener.accept() => res,
+                res = listener_v6.accept() => res,
+            },
+            None => tokio::select! {
+                url = callback_rx.recv() => return url.context("Callback server stopped")?,
+                res = listener.accept() => res,
+            },
+        };
+        let (socket, _) = accepted.context("Failed accepting connection")?;
+
+        let callback_tx = callback_tx.clone();
+        tokio::spawn(async move {
+            if let Some(url) = handle_callback_connection(socket, port).await {
+                // Only the first callback is received, later ones are dropped
+                let _ = callback_tx.send(url).await;
+            }
+        });
+    }
+}
 
-    // Get the first line from the buffer, that being the URL
-    let url = request
-        .lines()
-        .next()
-        .and_then(|line| line.split_whitespace().nth(1))
-        .context("Failed to extract URL from the request")?;
+/// Handle a connection to the callback server, returning the full callback URL if it is one
+///
+/// Connections which don't send a proper callback request are answered or dropped
+async fn handle_callback_connection(mut socket: TcpStream, port: u16) -> Option<Result<String>> {
+    // Ignore connections which don't send a proper request
+    let request_line = read_request_line(&mut socket).await.ok()?;
+
+    // Get the URL from the request line, e.g. "GET /callback?code=... HTTP/1.1"
+    let url = request_line.split_whitespace().nth(1)?.to_string();
+
+    if !is_callback(&url) {
+        // Failing to respond only affects the stray request, so the error is ignored
+        let _ = socket
+            .write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n")
+            .await;
+        return None;
+    }
 
     // Create response
     let response = b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\n\r\n\
@@ -251,14 +487,75 @@ async fn run_callback_server() -> Result<String> {
         </body>";
 
     // Write the response
-    socket
-        .write_all(response)
-        .await
-        .context("Failed sending response from callback server")?;
+    if let Err(e) = socket.write_all(response).await {
+        return Some(Err(e).context("Failed sending response from callback server"));
+    }
 
     // Reconstruct the full URL
-    let full_url = format!("http://localhost:{}{}", CALLBACK_SERVER_PORT, url);
+    Some(Ok(format!("http://localhost:{}{}", port, url)))
+}
+
+/// Read from the connection until the first line of the HTTP request is complete
+async fn read_request_line(socket: &mut TcpStream) -> Result<String> {
+    const MAX_REQUEST_LINE_LENGTH: usize = 8192;
+
+    let mut request = Vec::new();
+    let mut buffer = [0; 1024];
+
+    loop {
+        // Read from socket into buffer, store amount of read bytes
+        let n = socket
+            .read(&mut buffer)
+            .await
+            .context("Failed reading bytes from connection to callback server")?;
+
+        if n == 0 {
+            return Err(anyhow!("Connection closed before the request was complete"));
+        }
+
+        request.extend_from_slice(&buffer[..n]);
+
+        if let Some(end) = request.windows(2).position(|w| w == b"\r\n") {
+            let line = str::from_utf8(&request[..end]).context("Callback URL is malformed")?;
+            return Ok(line.to_string());
+        }
+
+        if request.len() > MAX_REQUEST_LINE_LENGTH {
+            return Err(anyhow!("Request sent to the callback server is too long"));
+        }
+    }
+}
+
+/// Is the URL the callback from spotify, carrying either the code or an error
+fn is_callback(url: &str) -> bool {
+    match url.split_once('?') {
+        Some(("/callback", query)) => query
+            .split('&')
+            .any(|param| param.starts_with("code=") || param.starts_with("error=")),
+        _ => false,
+    }
+}
 
-    // Finally return the URL
-    Ok(full_url)
+/// Bind the listener for the callback server
+///
+/// Retries a few times, since the port may only be held transiently, for example by a lingering
+/// browser connection from a previous authorization attempt
+async fn bind_callback_server(address: IpAddr, port: u16) -> Result<TcpListener> {
+    let mut attempt = 1;
+
+    loop {
+        match TcpListener::bind((address, port)).await {
+            Ok(listener) => return Ok(listener),
+            Err(e)
+                if e.kind() == ErrorKind::AddrInUse && attempt < CALLBACK_SERVER_BIND_ATTEMPTS =>
+            {
+                tokio::time::sleep(CALLBACK_SERVER_BIND_RETRY_DELAY).await;
+                attempt += 1;
+            }
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed running callback server on port {}", port))
+            }
+        }
+    }
 }
@@ -0,0 +1,84 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use rspotify::model::{Market, SearchResult, SearchType};
+use serde::{Deserialize, Serialize};
+
+use crate::auth;
+
+/// A search result stored in the search cache
+#[derive(Serialize, Deserialize)]
+struct CachedSearch {
+    /// When the result was stored, in seconds since the unix epoch
+    stored_at: u64,
+    result: SearchResult,
+}
+
+/// Get the search cache storage path
+fn search_cache_path() -> PathBuf {
+    let mut search_cache_path = auth::data_dir();
+    search_cache_path.push("search_cache.json");
+    search_cache_path
+}
+
+/// Get the key a search is cached under
+pub fn search_key(
+    query: &str,
+    search_type: SearchType,
+    market: Option<Market>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+) -> String {
+    format!(
+        "{}|{:?}|{:?}|{:?}|{:?}",
+        query, search_type, market, limit, offset
+    )
+}
+
+/// Get a cached search result, as long as it is younger than the TTL
+pub fn load_search(key: &str, ttl: Duration) -> Option<SearchResult> {
+    let cached = load_all().remove(key)?;
+
+    (now().saturating_sub(cached.stored_at) < ttl.as_secs()).then_some(cached.result)
+}
+
+/// Store a search result, removing all results older than the TTL
+///
+/// Caching is only an optimization, so failing to store the result is ignored
+pub fn store_search(key: String, result: SearchResult, ttl: Duration) {
+    let now = now();
+
+    let mut cache = load_all();
+    cache.retain(|_, cached| now.saturating_sub(cached.stored_at) < ttl.as_secs());
+    cache.insert(
+        key,
+        CachedSearch {
+            stored_at: now,
+            result,
+        },
+    );
+
+    if let (Ok(()), Ok(cache_str)) = (auth::ensure_dir(), serde_json::to_string(&cache)) {
+        let _ = fs::write(search_cache_path(), cache_str);
+    }
+}
+
+/// Load every cached search result, a missing or corrupt cache counts as empty
+fn load_all() -> HashMap<String, CachedSearch> {
+    fs::read_to_string(search_cache_path())
+        .ok()
+        .and_then(|cache_str| serde_json::from_str(&cache_str).ok())
+        .unwrap_or_default()
+}
+
+/// Get the current time in seconds since the unix epoch
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
@@ -1,22 +1,118 @@
-use std::time::{Duration, Instant};
+use std::{
+    collections::HashMap,
+    fs,
+    future::Future,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 
 use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use futures::future::try_join_all;
 use rspotify::{
+    http::HttpError,
     model::{
-        AdditionalType, CurrentPlaybackContext, Device, PlayableItem, RepeatState, SearchResult,
-        SearchType,
+        AdditionalType, AlbumId, ArtistId, CurrentPlaybackContext, Device, EpisodeId, FullPlaylist,
+        FullTrack, Market, Offset, PlayContextId, PlayableId, PlayableItem, PlaylistId,
+        PrivateUser, RecommendationsAttribute, RepeatState, SearchResult, SearchType, ShowId,
+        SimplifiedTrack, SubscriptionLevel, TimeLimits, TimeRange, TrackId, UserId,
     },
-    prelude::{BaseClient, OAuthClient},
-    AuthCodePkceSpotify,
+    prelude::{BaseClient, Id, OAuthClient},
+    AuthCodePkceSpotify, ClientError,
 };
+use serde::{Deserialize, Serialize};
+use tokio::sync::OnceCell;
 
 use crate::{
-    model::{Playable, Track},
+    auth, cache,
+    error::Error,
+    model::{Detailed, Playable, Status, Track, UserInfo},
     ui,
 };
 
 const DEVICE_CACHE_VALIDITY: Duration = Duration::from_secs(3);
 
+// The delay before retrying a rate limited request, if spotify doesn't tell us how long to wait.
+// Doubled with every attempt
+const RATE_LIMIT_BASE_DELAY: Duration = Duration::from_secs(1);
+
+// Rate limited requests are not retried if spotify asks us to wait longer than this
+const RATE_LIMIT_MAX_DELAY: Duration = Duration::from_secs(30);
+
+// How long a single request may take, unless another timeout is configured
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+// How often the playback is polled when waiting for spotify to apply a change
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// How long to wait for spotify to apply a change, when the user asked to wait for it
+const WAIT_TIME: Duration = Duration::from_secs(5);
+
+// How long to wait for spotify to switch the playback device
+const DEVICE_WAIT_TIME: Duration = Duration::from_secs(1);
+
+// How long to wait for the playback context to switch, before turning shuffle on
+const SHUFFLE_WAIT_TIME: Duration = Duration::from_secs(2);
+
+// Spotify returns at most this many search results per request
+const SEARCH_PAGE_SIZE: u32 = 50;
+
+// Spotify rejects search requests for results beyond this offset
+const SEARCH_MAX_OFFSET: u32 = 1000;
+
+// How long a track has to be playing, before smart previous restarts it instead of going back
+const SMART_PREV_RESTART_THRESHOLD: chrono::Duration = chrono::Duration::seconds(3);
+// Spotify returns at most this many recently played tracks per request
+const RECENTLY_PLAYED_PAGE_SIZE: u32 = 50;
+// Spotify adds at most this many items to a playlist per request
+const PLAYLIST_ADD_LIMIT: usize = 100;
+// Spotify returns at most this many liked songs per request
+const LIKED_FALLBACK_LIMIT: u32 = 50;
+
+/// Filters applied to search results
+#[derive(Clone, Copy, Default)]
+pub struct SearchFilter {
+    /// Filter out explicit results
+    pub clean: bool,
+    /// Collapse tracks with the same name and primary artist into the most popular one
+    pub dedup: bool,
+}
+
+/// What toggling starts playing, if nothing has played on the device yet
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToggleFallback {
+    /// Fail, like resuming does
+    Off,
+    /// Resume the most recently played context
+    #[default]
+    Recent,
+    /// Play the liked songs
+    Liked,
+}
+
+/// What is currently playing, used to detect playback changes
+#[derive(Default)]
+pub struct PlaybackSnapshot {
+    context_uri: Option<String>,
+    item_uri: Option<String>,
+}
+
+impl From<&CurrentPlaybackContext> for PlaybackSnapshot {
+    fn from(current_playback: &CurrentPlaybackContext) -> Self {
+        let item_uri = match &current_playback.item {
+            Some(PlayableItem::Track(track)) => track.id.as_ref().map(|id| id.uri()),
+            Some(PlayableItem::Episode(episode)) => Some(episode.id.uri()),
+            _ => None,
+        };
+
+        Self {
+            context_uri: current_playback.context.as_ref().map(|c| c.uri.clone()),
+            item_uri,
+        }
+    }
+}
+
 // Struct for caching the current playback device
 struct CachedDevice {
     _device: Device, // We currently don't need the device, but no reason to not save it
@@ -42,6 +138,15 @@ impl CachedDevice {
 pub struct SpotifyPlayer {
     client: AuthCodePkceSpotify,
     cached_device: Option<CachedDevice>,
+    cached_user: OnceCell<PrivateUser>,
+    max_retries: u32,
+    default_device: Option<String>,
+    dry_run: bool,
+    clean_fallback: bool,
+    smart_prev: bool,
+    toggle_fallback: ToggleFallback,
+    search_cache_ttl: Duration,
+    request_timeout: Duration,
 }
 
 impl SpotifyPlayer {
@@ -50,35 +155,207 @@ impl SpotifyPlayer {
         Self {
             client,
             cached_device: None,
+            cached_user: OnceCell::new(),
+            max_retries: 0,
+            default_device: None,
+            dry_run: false,
+            clean_fallback: false,
+            smart_prev: false,
+            toggle_fallback: ToggleFallback::default(),
+            search_cache_ttl: Duration::ZERO,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
         }
     }
 
+    /// Set how often rate limited requests are retried
+    pub fn set_max_retries(&mut self, max_retries: u32) {
+        self.max_retries = max_retries;
+    }
+
+    /// Set the name or ID of the playback device preferred if no device is active
+    pub fn set_default_device(&mut self, default_device: Option<String>) {
+        self.default_device = default_device;
+    }
+
+    /// Set whether explicit results are kept, if filtering them out would remove every result
+    pub fn set_clean_fallback(&mut self, clean_fallback: bool) {
+        self.clean_fallback = clean_fallback;
+    }
+
+    /// Set whether going to the previous track restarts the current track if it played for a while
+    pub fn set_smart_prev(&mut self, smart_prev: bool) {
+        self.smart_prev = smart_prev;
+    }
+
+    /// Set what toggling starts playing, if nothing has played on the device yet
+    pub fn set_toggle_fallback(&mut self, toggle_fallback: ToggleFallback) {
+        self.toggle_fallback = toggle_fallback;
+    }
+
+    /// Set how long a single request may take before it fails
+    pub fn set_request_timeout(&mut self, request_timeout: Duration) {
+        self.request_timeout = request_timeout;
+    }
+
+    /// Set how long search results are cached, a TTL of zero disables the search cache
+    pub fn set_search_cache_ttl(&mut self, search_cache_ttl: Duration) {
+        self.search_cache_ttl = search_cache_ttl;
+    }
+
+    /// Set whether changes are only printed instead of being sent to spotify
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run = dry_run;
+    }
+
+    /// Are changes only printed instead of being sent to spotify
+    pub fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+
     /// Get the currently playing track
+    ///
+    /// Returns Ok(None) if nothing is playing, including when there is no active playback device
     pub async fn current_track(&mut self) -> Result<Option<Track>> {
-        self.ensure_device().await?;
+        // Outputting the current track doesn't need a device, so the user isn't asked to select one
+        if !self.has_active_device().await? {
+            return Ok(None);
+        }
 
-        let currently_playing = self
-            .client
-            .current_playing(None, None::<Option<&AdditionalType>>)
+        let Some(currently_playing) = self
+            .with_retry(|| {
+                self.client
+                    .current_playing(None, None::<Option<&AdditionalType>>)
+            })
             .await
             .context("Failed getting the current track")?
-            .context("Current track is unknown")?;
+        else {
+            return Ok(None);
+        };
 
         if !currently_playing.is_playing {
             return Ok(None);
         }
 
-        return match currently_playing.item {
-            Some(PlayableItem::Track(track)) => Ok(Some(Track {
-                title: track.name,
-                by: track.artists.iter().map(|a| a.name.clone()).collect(),
-            })),
+        if let Some(context) = &currently_playing.context {
+            save_last_context(&context.uri);
+        }
+
+        match currently_playing.item {
+            Some(PlayableItem::Track(track)) => Ok(Some(Track::from(track))),
             Some(PlayableItem::Episode(episode)) => Ok(Some(Track {
                 title: episode.name,
                 by: vec![episode.show.name],
+                id: Some(PlayableId::from(episode.id)),
+                cover_images: episode.images,
             })),
             _ => Ok(None),
+        }
+    }
+
+    /// Get the current track using a single request, without checking for a playback device
+    ///
+    /// Returns Ok(None) if nothing is playing
+    pub async fn now_playing(&mut self) -> Result<Option<Track>> {
+        let currently_playing = self
+            .with_retry(|| {
+                self.client
+                    .current_playing(None, None::<Option<&AdditionalType>>)
+            })
+            .await
+            .context("Failed getting the current track")?;
+
+        Ok(currently_playing
+            .filter(|c| c.is_playing)
+            .and_then(|c| c.item)
+            .map(Track::from))
+    }
+
+    /// Get the full current playback state from spotify, without mapping it
+    ///
+    /// Returns Ok(None) if nothing is playing
+    pub async fn raw_playback(&mut self) -> Result<Option<CurrentPlaybackContext>> {
+        self.with_retry(|| {
+            self.client
+                .current_playback(None, None::<Option<&AdditionalType>>)
+        })
+        .await
+        .context("Failed determining current playback state")
+    }
+
+    /// Get the bare ID of the current item, also if it is paused
+    ///
+    /// Local files have no ID, so nothing is returned for them
+    pub async fn current_id(&mut self) -> Result<Option<String>> {
+        let id = self.status().await?.and_then(|status| status.track.id);
+
+        Ok(id.map(|id| id.id().to_string()))
+    }
+
+    /// Get the current playback state without selecting a playback device
+    ///
+    /// Returns Ok(None) if there is no active playback device or nothing is loaded
+    pub async fn status(&mut self) -> Result<Option<Status>> {
+        let current_playback = self
+            .with_retry(|| {
+                self.client
+                    .current_playback(None, None::<Option<&AdditionalType>>)
+            })
+            .await
+            .context("Failed determining current playback state")?;
+
+        let current_playback = match current_playback {
+            Some(current_playback) => current_playback,
+            None => return Ok(None),
+        };
+
+        if let (true, Some(context)) = (current_playback.is_playing, &current_playback.context) {
+            save_last_context(&context.uri);
+        }
+
+        let (track, duration) = match current_playback.item {
+            Some(PlayableItem::Track(track)) => {
+                let duration = track.duration;
+                (Track::from(track), duration)
+            }
+            Some(PlayableItem::Episode(episode)) => (
+                Track {
+                    title: episode.name,
+                    by: vec![episode.show.name],
+                    id: Some(PlayableId::from(episode.id)),
+                    cover_images: episode.images,
+                },
+                episode.duration,
+            ),
+            _ => return Ok(None),
+        };
+
+        Ok(Some(Status {
+            track,
+            is_playing: current_playback.is_playing,
+            progress: current_playback.progress.and_then(|p| p.to_std().ok()),
+            duration: duration.to_std().ok(),
+        }))
+    }
+
+    /// Get the open.spotify.com URL of the currently playing item
+    ///
+    /// Returns Ok(None) if nothing is playing
+    pub async fn current_uri(&mut self) -> Result<Option<String>> {
+        let current_playback = self
+            .with_retry(|| {
+                self.client
+                    .current_playback(None, None::<Option<&AdditionalType>>)
+            })
+            .await
+            .context("Failed determining current playback state")?;
+
+        let url = match current_playback.and_then(|p| p.item) {
+            Some(PlayableItem::Track(track)) => track.external_urls.get("spotify").cloned(),
+            Some(PlayableItem::Episode(episode)) => episode.external_urls.get("spotify").cloned(),
+            _ => None,
         };
+
+        Ok(url)
     }
 
     /// Pause the playback
@@ -87,9 +364,8 @@ impl SpotifyPlayer {
 
         let current_playback = self.playback_context().await?;
 
-        if current_playback.is_playing {
-            self.client
-                .pause_playback(None)
+        if current_playback.is_playing && !self.skip_dry_run("pause playback") {
+            self.with_retry(|| self.client.pause_playback(None))
                 .await
                 .context("Failed pausing playback")?;
         }
@@ -103,9 +379,8 @@ impl SpotifyPlayer {
 
         let current_playback = self.playback_context().await?;
 
-        if !current_playback.is_playing {
-            self.client
-                .resume_playback(None, None)
+        if !current_playback.is_playing && !self.skip_dry_run("resume playback") {
+            self.with_retry(|| self.client.resume_playback(None, None))
                 .await
                 .context("Failed resuming playback")?;
         }
@@ -117,176 +392,1353 @@ impl SpotifyPlayer {
     pub async fn playback_toggle(&mut self) -> Result<()> {
         self.ensure_device().await?;
 
-        let current_playback = self.playback_context().await?;
+        let current_playback = self
+            .with_retry(|| {
+                self.client
+                    .current_playback(None, None::<Option<&AdditionalType>>)
+            })
+            .await
+            .context("Failed determining current playback state")?;
+
+        // Without a current item there is nothing to resume, e.g. on a freshly started device
+        let Some(current_playback) = current_playback.filter(|p| p.item.is_some()) else {
+            return self.playback_fallback().await;
+        };
+
+        let action = match current_playback.is_playing {
+            true => "pause playback",
+            false => "resume playback",
+        };
+
+        if self.skip_dry_run(action) {
+            return Ok(());
+        }
+
+        self.with_retry(|| match current_playback.is_playing {
+            true => self.client.pause_playback(None),
+            false => self.client.resume_playback(None, None),
+        })
+        .await
+        .context("Failed toggling playback")?;
+
+        Ok(())
+    }
+
+    /// Start playing something according to the toggle fallback
+    async fn playback_fallback(&mut self) -> Result<()> {
+        match self.toggle_fallback {
+            ToggleFallback::Off => Err(Error::NothingPlaying.into()),
+            ToggleFallback::Recent => self.play_last_context().await,
+            ToggleFallback::Liked => self.play_liked().await,
+        }
+    }
+
+    /// Resume the most recently played context, at the track played last
+    ///
+    /// Plays only that track if it wasn't played from a context
+    async fn play_last_context(&mut self) -> Result<()> {
+        let last = self
+            .with_retry(|| self.client.current_user_recently_played(Some(1), None))
+            .await
+            .context("Failed getting recently played tracks")?
+            .items
+            .into_iter()
+            .next()
+            .ok_or(anyhow!(
+                "Nothing played recently, so there is nothing to resume"
+            ))?;
+
+        let track_id = last
+            .track
+            .id
+            .context("The last played track can't be played")?;
+        let context = last.context.and_then(|context| context_id(&context.uri));
+
+        // Spotify only accepts an offset for albums and playlists, artists and shows start at the
+        // beginning instead
+        let offset = match &context {
+            Some(PlayContextId::Album(_) | PlayContextId::Playlist(_)) => {
+                Some(Offset::Uri(track_id.uri()))
+            }
+            _ => None,
+        };
+
+        let action = match &context {
+            Some(context) => format!("resume {}", context.uri()),
+            None => format!("play {}", track_id.uri()),
+        };
+
+        if self.skip_dry_run(&action) {
+            return Ok(());
+        }
+
+        self.with_retry(|| match &context {
+            Some(context) => {
+                self.client
+                    .start_context_playback(context.clone(), None, offset.clone(), None)
+            }
+            None => self.client.start_uris_playback(
+                [PlayableId::Track(track_id.clone())],
+                None,
+                None,
+                None,
+            ),
+        })
+        .await
+        .context("Failed resuming the last played context")?;
+
+        Ok(())
+    }
+
+    /// Play the most recently liked songs
+    async fn play_liked(&mut self) -> Result<()> {
+        let ids: Vec<PlayableId> = self
+            .with_retry(|| {
+                self.client
+                    .current_user_saved_tracks_manual(None, Some(LIKED_FALLBACK_LIMIT), None)
+            })
+            .await
+            .context("Failed getting liked songs")?
+            .items
+            .into_iter()
+            .filter_map(|saved| saved.track.id)
+            .map(PlayableId::from)
+            .collect();
+
+        if ids.is_empty() {
+            return Err(anyhow!("No liked songs to play"));
+        }
+
+        if self.skip_dry_run(&format!("play {} liked songs", ids.len())) {
+            return Ok(());
+        }
+
+        self.with_retry(|| {
+            self.client
+                .start_uris_playback(ids.clone(), None, None, None)
+        })
+        .await
+        .context("Failed playing liked songs")?;
+
+        Ok(())
+    }
+
+    /// Get the current volume in percent
+    pub async fn volume_get(&mut self) -> Result<u8> {
+        self.ensure_device().await?;
+
+        let device = self.playback_context().await?.device;
+
+        // Some devices, e.g. certain speakers, don't report their volume
+        let volume = device.volume_percent.with_context(|| {
+            format!(
+                "The playback device \"{}\" doesn't report its volume",
+                device.name
+            )
+        })?;
+
+        Ok(volume as u8)
+    }
+
+    /// Set the current volume in percent
+    ///
+    /// Doesn't require the current volume, so this also works on devices which don't report it
+    pub async fn volume_set(&mut self, volume: u8) -> Result<()> {
+        self.ensure_device().await?;
+
+        if self.skip_dry_run(&format!("set the volume to {}%", volume.clamp(0, 100))) {
+            return Ok(());
+        }
+
+        self.with_retry(|| self.client.volume(volume.clamp(0, 100), None))
+            .await
+            .context("Failed setting volume")?;
+
+        Ok(())
+    }
+
+    /// Increase volume by given percentage
+    pub async fn volume_up(&mut self, up: u8) -> Result<()> {
+        let volume = self.volume_relative_base().await?;
+
+        self.volume_set(volume + up.min(100 - volume)).await
+    }
+
+    /// Decrease volume by given percentage
+    pub async fn volume_down(&mut self, down: u8) -> Result<()> {
+        let volume = self.volume_relative_base().await?;
+
+        self.volume_set(volume - down.min(volume)).await
+    }
+
+    /// Scale the volume to a percentage of the current volume, e.g. 110 for 10% louder
+    pub async fn volume_scale(&mut self, percent: u32) -> Result<()> {
+        let volume = self.volume_relative_base().await? as u32;
+
+        // Round to the nearest whole percentage point
+        let scaled = (volume * percent + 50) / 100;
+
+        self.volume_set(scaled.min(100) as u8).await
+    }
+
+    /// Get the current volume, which relative volume changes are based on
+    async fn volume_relative_base(&mut self) -> Result<u8> {
+        self.ensure_device().await?;
+
+        let device = self.playback_context().await?.device;
+
+        match device.volume_percent {
+            Some(volume) => Ok(volume as u8),
+            None => Err(anyhow!(
+                "The playback device \"{}\" doesn't report its volume, so it can't be changed relatively. Set an absolute volume instead, e.g. \"sc volume 50\"",
+                device.name
+            )),
+        }
+    }
+
+    /// Seek to a position in the current track
+    pub async fn seek(&mut self, position: Duration) -> Result<()> {
+        self.ensure_device().await?;
+
+        let position = chrono::Duration::from_std(position).context("Invalid seek position")?;
+
+        if self.skip_dry_run(&format!("seek to {}s", position.num_seconds())) {
+            return Ok(());
+        }
+
+        self.with_retry(|| self.client.seek_track(position, None))
+            .await
+            .context("Failed seeking")?;
+
+        Ok(())
+    }
+
+    /// Seek to the beginning of the current track
+    ///
+    /// Returns false if nothing is playing
+    pub async fn restart(&mut self) -> Result<bool> {
+        self.ensure_device().await?;
+
+        if self.status().await?.is_none() {
+            return Ok(false);
+        }
+
+        self.seek(Duration::ZERO).await?;
+
+        Ok(true)
+    }
+
+    /// Pause the playback and seek to the beginning of the current track
+    ///
+    /// Spotify has no way of stopping playback, so this is the closest equivalent. Returns false
+    /// if nothing is playing
+    pub async fn stop(&mut self) -> Result<bool> {
+        self.ensure_device().await?;
+
+        if self.status().await?.is_none() {
+            return Ok(false);
+        }
+
+        self.playback_pause().await?;
+        self.seek(Duration::ZERO).await?;
+
+        Ok(true)
+    }
+
+    /// Search for content by using a search query and specifying the search types
+    ///
+    /// The limit is the amount of results per search type. Spotify returns at most 50 results per
+    /// request, so larger limits are fetched using multiple requests, until the limit or the end of
+    /// the results is reached. The results are concatenated in the order of the given types. The
+    /// filter is applied to the results afterwards
+    pub async fn search(
+        &mut self,
+        query: String,
+        search_types: Vec<SearchType>,
+        market: Option<Market>,
+        limit: Option<u32>,
+        offset: Option<u32>,
+        filter: SearchFilter,
+    ) -> Result<Vec<Box<dyn Playable + 'static>>> {
+        // Searching doesn't need a device, checking for one only warms the cache for playing a
+        // result afterwards
+        self.has_active_device().await?;
+
+        // Results in the user's country are more relevant and can actually be played
+        let market = match market {
+            Some(market) => Some(market),
+            None => self.user_market().await?,
+        };
+
+        // Every type is searched concurrently, the results are still ordered like the types
+        let results: Vec<Box<dyn Playable>> =
+            try_join_all(search_types.iter().map(|search_type| {
+                self.search_type(&query, *search_type, market, limit, offset, filter.dedup)
+            }))
+            .await?
+            .into_iter()
+            .flatten()
+            .collect();
+
+        if filter.clean {
+            let (explicit, clean_results): (Vec<_>, Vec<_>) =
+                results.into_iter().partition(|r| r.is_explicit());
+
+            // Every result was explicit, so the explicit results are all results
+            if clean_results.is_empty() && !explicit.is_empty() && self.clean_fallback {
+                eprintln!("No clean matches found, including explicit matches");
+                return Ok(explicit);
+            }
+
+            return Ok(clean_results);
+        }
+
+        Ok(results)
+    }
+
+    /// Search a single type, fetching as many pages as needed to reach the limit
+    async fn search_type(
+        &self,
+        query: &str,
+        search_type: SearchType,
+        market: Option<Market>,
+        limit: Option<u32>,
+        offset: Option<u32>,
+        dedup: bool,
+    ) -> Result<Vec<Box<dyn Playable + 'static>>> {
+        fn map_playable<T: Playable + 'static>(items: Vec<T>) -> Vec<Box<dyn Playable>> {
+            items
+                .into_iter()
+                .map(|item| Box::new(item) as Box<dyn Playable>)
+                .collect()
+        }
+
+        let mut results: Vec<Box<dyn Playable>> = Vec::new();
+
+        // Tracks are collected across pages, so duplicates on different pages are collapsed
+        let mut tracks: Vec<FullTrack> = Vec::new();
+        let mut fetched = 0;
+
+        loop {
+            let page_offset = offset.unwrap_or(0) + fetched;
+
+            // Spotify rejects requests beyond the maximum offset, so stop there
+            let page_limit = limit.map(|limit| {
+                limit
+                    .saturating_sub(fetched)
+                    .min(SEARCH_PAGE_SIZE)
+                    .min(SEARCH_MAX_OFFSET.saturating_sub(page_offset))
+            });
+
+            if page_limit == Some(0) {
+                break;
+            }
+
+            let search = self
+                .search_cached(
+                    query,
+                    search_type,
+                    market,
+                    page_limit,
+                    Some(page_offset).filter(|o| *o > 0),
+                )
+                .await?;
+
+            let (count, has_next) = match search {
+                SearchResult::Playlists(playlists) => {
+                    let page = (playlists.items.len(), playlists.next.is_some());
+                    results.extend(map_playable(playlists.items));
+                    page
+                }
+                SearchResult::Albums(albums) => {
+                    let page = (albums.items.len(), albums.next.is_some());
+                    results.extend(map_playable(albums.items));
+                    page
+                }
+                SearchResult::Artists(artists) => {
+                    let page = (artists.items.len(), artists.next.is_some());
+                    results.extend(map_playable(artists.items));
+                    page
+                }
+                SearchResult::Tracks(found) => {
+                    let page = (found.items.len(), found.next.is_some());
+                    tracks.extend(found.items);
+                    page
+                }
+                SearchResult::Shows(shows) => {
+                    let page = (shows.items.len(), shows.next.is_some());
+                    results.extend(map_playable(shows.items));
+                    page
+                }
+                SearchResult::Episodes(episodes) => {
+                    let page = (episodes.items.len(), episodes.next.is_some());
+                    results.extend(map_playable(episodes.items));
+                    page
+                }
+            };
+
+            fetched += count as u32;
+
+            // Without a limit, spotify decides how many results a single request returns
+            if limit.is_none() || !has_next || count == 0 {
+                break;
+            }
+        }
+
+        match dedup {
+            true => results.extend(map_playable(dedup_tracks(tracks))),
+            false => results.extend(map_playable(tracks)),
+        }
+
+        Ok(results)
+    }
+
+    /// Run a single search request, using the search cache if it is enabled
+    async fn search_cached(
+        &self,
+        query: &str,
+        search_type: SearchType,
+        market: Option<Market>,
+        limit: Option<u32>,
+        offset: Option<u32>,
+    ) -> Result<SearchResult> {
+        let key = cache::search_key(query, search_type, market, limit, offset);
+
+        if !self.search_cache_ttl.is_zero() {
+            if let Some(search) = cache::load_search(&key, self.search_cache_ttl) {
+                log::debug!("Using cached search results for \"{}\"", query);
+                return Ok(search);
+            }
+        }
+
+        let search = self
+            .with_retry(|| {
+                self.client
+                    .search(query, search_type, market, None, limit, offset)
+            })
+            .await
+            .context("Failed searching content")?;
+
+        if !self.search_cache_ttl.is_zero() {
+            cache::store_search(key, search.clone(), self.search_cache_ttl);
+        }
+
+        Ok(search)
+    }
+
+    /// Play a Playable item using the client
+    ///
+    /// Playlists and albums start at the track with the zero based offset, if one is given
+    pub async fn play(&mut self, item: &dyn Playable, offset: Option<u32>) -> Result<()> {
+        self.ensure_device().await?;
+
+        if self.skip_dry_run(&format!("play {}", item.to_display())) {
+            return Ok(());
+        }
+
+        self.with_retry(|| item.play(&self.client, offset))
+            .await
+            .context("Failed playing item")?;
+
+        // Contexts are remembered, so they can be resumed after switching devices
+        if let Some(uri) = item.to_summary().uri {
+            save_last_context(&uri);
+        }
+
+        Ok(())
+    }
+
+    /// Play a Playable item in shuffle mode
+    ///
+    /// Shuffle is only turned on once spotify has switched to the new playback context, since it
+    /// may otherwise only apply to the previous context
+    pub async fn play_shuffled(&mut self, item: &dyn Playable) -> Result<()> {
+        let before = self.snapshot().await;
+
+        self.play(item, None).await?;
+
+        // Continue anyways after timing out, e.g. if the same context was already playing
+        self.wait_for(SHUFFLE_WAIT_TIME, |p| {
+            PlaybackSnapshot::from(p).context_uri != before.context_uri
+        })
+        .await;
+
+        self.shuffle_on().await
+    }
+
+    /// Play an item by its Spotify URI, e.g. spotify:album:<id>
+    ///
+    /// Albums, artists, playlists and shows are played as a context, tracks and episodes alone
+    pub async fn play_uri(&mut self, uri: &str) -> Result<()> {
+        let context = context_id(uri);
+        let playable: Option<PlayableId> = match (TrackId::from_uri(uri), EpisodeId::from_uri(uri))
+        {
+            (Ok(id), _) => Some(id.into_static().into()),
+            (_, Ok(id)) => Some(id.into_static().into()),
+            _ => None,
+        };
+
+        if context.is_none() && playable.is_none() {
+            return Err(anyhow!("\"{}\" can't be played", uri));
+        }
+
+        self.ensure_device().await?;
+
+        if self.skip_dry_run(&format!("play {}", uri)) {
+            return Ok(());
+        }
+
+        self.with_retry(|| match (&context, &playable) {
+            (Some(context), _) => {
+                self.client
+                    .start_context_playback(context.clone(), None, None, None)
+            }
+            (_, playable) => self
+                .client
+                .start_uris_playback(playable.clone(), None, None, None),
+        })
+        .await
+        .context("Failed playing item")?;
+
+        if context.is_some() {
+            save_last_context(uri);
+        }
+
+        Ok(())
+    }
+
+    /// Play the top tracks of an artist instead of the artist context
+    ///
+    /// Spotify requires a market for top tracks, so the user's market is used if none is given
+    pub async fn play_artist_top(
+        &mut self,
+        artist_id: ArtistId<'static>,
+        market: Option<Market>,
+    ) -> Result<()> {
+        self.ensure_device().await?;
+
+        let market = match market {
+            Some(market) => market,
+            None => self.user_market().await?.unwrap_or(Market::FromToken),
+        };
+
+        let ids: Vec<PlayableId> = self
+            .with_retry(|| {
+                self.client
+                    .artist_top_tracks(artist_id.clone(), Some(market))
+            })
+            .await
+            .context("Failed getting the artist's top tracks")?
+            .into_iter()
+            .filter_map(|track| track.id)
+            .map(PlayableId::from)
+            .collect();
+
+        if ids.is_empty() {
+            return Err(anyhow!("No top tracks found for this artist"));
+        }
+
+        if self.skip_dry_run(&format!("play {} top tracks", ids.len())) {
+            return Ok(());
+        }
+
+        self.with_retry(|| {
+            self.client
+                .start_uris_playback(ids.clone(), None, None, None)
+        })
+        .await
+        .context("Failed playing top tracks")?;
+
+        Ok(())
+    }
+
+    /// Play a track within its album, so playback continues with the rest of the album
+    ///
+    /// The album starts at the track's URI rather than its position, since a track found in a
+    /// market may be a substitute with a different ID than the album lists
+    pub async fn play_in_album(&mut self, track: &dyn Playable) -> Result<()> {
+        let (Some(track_id), Some(album_id)) = (track.playable_id(), track.track_album_id()) else {
+            return Err(anyhow!("Only tracks can be played within their album"));
+        };
+
+        self.ensure_device().await?;
+
+        if self.skip_dry_run(&format!("play {} within its album", track.to_display())) {
+            return Ok(());
+        }
+
+        self.with_retry(|| {
+            self.client.start_context_playback(
+                PlayContextId::Album(album_id.clone()),
+                None,
+                Some(Offset::Uri(track_id.uri())),
+                None,
+            )
+        })
+        .await
+        .context("Failed playing track within its album")?;
+
+        save_last_context(&album_id.uri());
+
+        Ok(())
+    }
+
+    /// Get a snapshot of what is currently playing, used to wait for playback changes
+    pub async fn snapshot(&mut self) -> PlaybackSnapshot {
+        match self.playback_context().await {
+            Ok(current_playback) => PlaybackSnapshot::from(&current_playback),
+            Err(_) => PlaybackSnapshot::default(),
+        }
+    }
+
+    /// Wait until spotify plays an item, which was played after taking the snapshot
+    ///
+    /// Tracks and episodes are recognized directly, other items are recognized by the playback
+    /// context changing
+    pub async fn wait_for_playing(
+        &self,
+        item: &dyn Playable,
+        before: &PlaybackSnapshot,
+    ) -> Result<()> {
+        let item_uri = item.playable_id().map(|id| id.uri());
+
+        let applied = self
+            .wait_for(WAIT_TIME, |p| {
+                let now = PlaybackSnapshot::from(p);
+
+                p.is_playing
+                    && match &item_uri {
+                        Some(uri) => now.item_uri.as_ref() == Some(uri),
+                        None => now.context_uri != before.context_uri,
+                    }
+            })
+            .await;
+
+        match applied {
+            true => Ok(()),
+            false => Err(anyhow!("Timed out waiting for the item to start playing")),
+        }
+    }
+
+    /// Wait until spotify plays a different item than when taking the snapshot
+    pub async fn wait_for_item_change(&self, before: &PlaybackSnapshot) -> Result<()> {
+        let applied = self
+            .wait_for(WAIT_TIME, |p| {
+                PlaybackSnapshot::from(p).item_uri != before.item_uri
+            })
+            .await;
+
+        match applied {
+            true => Ok(()),
+            false => Err(anyhow!("Timed out waiting for the track to change")),
+        }
+    }
+
+    /// Wait until a different track plays, checking the playback every interval
+    ///
+    /// Returns the new status, or Ok(None) once playback is paused or stopped
+    pub async fn wait_for_track_change(&mut self, interval: Duration) -> Result<Option<Status>> {
+        let before = match self.status().await? {
+            Some(status) if status.is_playing => status.track.id,
+            _ => return Err(Error::NothingPlaying.into()),
+        };
+
+        loop {
+            tokio::time::sleep(interval).await;
+
+            match self.status().await? {
+                Some(status) if !status.is_playing => return Ok(None),
+                Some(status) if status.track.id != before => return Ok(Some(status)),
+                Some(_) => {}
+                None => return Ok(None),
+            }
+        }
+    }
+
+    /// Add a Playable item to the playback queue
+    pub async fn add_to_queue(&mut self, item: &dyn Playable) -> Result<()> {
+        self.ensure_device().await?;
+
+        let id = item.playable_id().with_context(|| {
+            format!(
+                "Only tracks and episodes can be queued ({})",
+                item.type_string()
+            )
+        })?;
+
+        if self.skip_dry_run(&format!("queue {}", item.to_display())) {
+            return Ok(());
+        }
+
+        self.with_retry(|| self.client.add_item_to_queue(id.clone(), None))
+            .await
+            .context("Failed adding item to queue")?;
+
+        Ok(())
+    }
+
+    /// Follow a Playable item using the client
+    pub async fn follow(&mut self, item: &dyn Playable) -> Result<()> {
+        if self.skip_dry_run(&format!("follow {}", item.to_display())) {
+            return Ok(());
+        }
+
+        self.with_retry(|| item.follow(&self.client))
+            .await
+            .context("Failed following item")?;
+
+        Ok(())
+    }
+
+    /// Unfollow a Playable item using the client
+    pub async fn unfollow(&mut self, item: &dyn Playable) -> Result<()> {
+        if self.skip_dry_run(&format!("unfollow {}", item.to_display())) {
+            return Ok(());
+        }
+
+        self.with_retry(|| item.unfollow(&self.client))
+            .await
+            .context("Failed unfollowing item")?;
+
+        Ok(())
+    }
+
+    /// Create a new empty playlist in the users library
+    ///
+    /// Returns Ok(None) if this is a dry run
+    pub async fn create_playlist(
+        &mut self,
+        name: &str,
+        public: bool,
+        description: Option<&str>,
+    ) -> Result<Option<FullPlaylist>> {
+        if self.skip_dry_run(&format!("create the playlist \"{}\"", name)) {
+            return Ok(None);
+        }
+
+        let user_id = self.user_id().await?;
+
+        let playlist = self
+            .with_retry(|| {
+                self.client.user_playlist_create(
+                    user_id.clone(),
+                    name,
+                    Some(public),
+                    None,
+                    description,
+                )
+            })
+            .await
+            .context("Failed creating playlist")?;
+
+        Ok(Some(playlist))
+    }
+
+    /// Get all tracks of an album
+    pub async fn album_tracks(
+        &mut self,
+        album_id: AlbumId<'static>,
+    ) -> Result<Vec<Box<dyn Playable + 'static>>> {
+        Ok(self
+            .all_album_tracks(album_id)
+            .await?
+            .into_iter()
+            .map(|track| Box::new(track) as Box<dyn Playable>)
+            .collect())
+    }
+
+    /// Fetch every page of an album's tracks
+    async fn all_album_tracks(&self, album_id: AlbumId<'static>) -> Result<Vec<SimplifiedTrack>> {
+        const PAGE_SIZE: u32 = 50;
+
+        let mut tracks = Vec::new();
+        let mut offset = 0;
+
+        // Albums may have more tracks than fit into a single page
+        loop {
+            let page = self
+                .with_retry(|| {
+                    self.client.album_track_manual(
+                        album_id.clone(),
+                        None,
+                        Some(PAGE_SIZE),
+                        Some(offset),
+                    )
+                })
+                .await
+                .context("Failed getting album tracks")?;
+
+            tracks.extend(page.items);
+
+            if page.next.is_none() {
+                return Ok(tracks);
+            }
+
+            offset += PAGE_SIZE;
+        }
+    }
+
+    /// Display albums and playlists with their track count and total duration
+    ///
+    /// Fetches every track of each album and playlist, so this is slow for many or long ones.
+    /// Other items are kept as they are
+    pub async fn with_details(
+        &mut self,
+        items: Vec<Box<dyn Playable + 'static>>,
+    ) -> Result<Vec<Box<dyn Playable + 'static>>> {
+        let mut detailed: Vec<Box<dyn Playable>> = Vec::with_capacity(items.len());
+
+        for item in items {
+            let totals = match (item.album_id(), item.playlist_id()) {
+                (Some(album_id), _) => Some(self.album_totals(album_id).await?),
+                (_, Some(playlist_id)) => Some(self.playlist_totals(playlist_id).await?),
+                _ => None,
+            };
+
+            detailed.push(match totals {
+                Some((tracks, duration)) => Box::new(Detailed {
+                    item,
+                    tracks,
+                    duration,
+                }),
+                None => item,
+            });
+        }
+
+        Ok(detailed)
+    }
+
+    /// Get the track count and total duration of an album
+    async fn album_totals(&mut self, album_id: AlbumId<'static>) -> Result<(u32, Duration)> {
+        let tracks = self.all_album_tracks(album_id).await?;
+
+        let duration = tracks
+            .iter()
+            .filter_map(|track| track.duration.to_std().ok())
+            .sum();
+
+        Ok((tracks.len() as u32, duration))
+    }
+
+    /// Get the track count and total duration of a playlist
+    async fn playlist_totals(
+        &mut self,
+        playlist_id: PlaylistId<'static>,
+    ) -> Result<(u32, Duration)> {
+        const PAGE_SIZE: u32 = 100;
+
+        let mut duration = Duration::ZERO;
+        let mut offset = 0;
+
+        loop {
+            let page = self
+                .with_retry(|| {
+                    self.client.playlist_items_manual(
+                        playlist_id.clone(),
+                        None,
+                        None,
+                        Some(PAGE_SIZE),
+                        Some(offset),
+                    )
+                })
+                .await
+                .context("Failed getting playlist tracks")?;
+
+            duration += page
+                .items
+                .iter()
+                .filter_map(|item| match &item.track {
+                    Some(PlayableItem::Track(track)) => track.duration.to_std().ok(),
+                    Some(PlayableItem::Episode(episode)) => episode.duration.to_std().ok(),
+                    None => None,
+                })
+                .sum();
+
+            if page.next.is_none() {
+                return Ok((page.total, duration));
+            }
+
+            offset += PAGE_SIZE;
+        }
+    }
+
+    /// Get all episodes of a show, the newest episode first
+    pub async fn show_episodes(
+        &mut self,
+        show_id: ShowId<'static>,
+    ) -> Result<Vec<Box<dyn Playable + 'static>>> {
+        const PAGE_SIZE: u32 = 50;
+
+        let mut episodes: Vec<Box<dyn Playable>> = Vec::new();
+        let mut offset = 0;
+
+        // Shows may have more episodes than fit into a single page
+        loop {
+            let page = self
+                .with_retry(|| {
+                    self.client.get_shows_episodes_manual(
+                        show_id.clone(),
+                        None,
+                        Some(PAGE_SIZE),
+                        Some(offset),
+                    )
+                })
+                .await
+                .context("Failed getting show episodes")?;
+
+            episodes.extend(
+                page.items
+                    .into_iter()
+                    .map(|item| Box::new(item) as Box<dyn Playable>),
+            );
+
+            if page.next.is_none() {
+                break;
+            }
+
+            offset += PAGE_SIZE;
+        }
+
+        Ok(episodes)
+    }
+
+    /// Get information about the current user
+    pub async fn me(&mut self) -> Result<UserInfo> {
+        Ok(UserInfo::from(self.current_user().await?))
+    }
+
+    /// Get the ID of the current user
+    pub async fn user_id(&mut self) -> Result<UserId<'static>> {
+        Ok(self.current_user().await?.id)
+    }
+
+    /// Get the market of the current user's country, if spotify knows it
+    pub async fn user_market(&mut self) -> Result<Option<Market>> {
+        Ok(self.current_user().await?.country.map(Market::Country))
+    }
+
+    /// Get the current user, which is only requested once and cached afterwards
+    async fn current_user(&self) -> Result<PrivateUser> {
+        self.cached_user
+            .get_or_try_init(|| async {
+                // Requesting the user is never a Premium problem, which would also recurse here
+                self.retry(|| self.client.current_user())
+                    .await
+                    .context("Failed getting the current user")
+            })
+            .await
+            .cloned()
+    }
+
+    /// Get the playlists in users library
+    ///
+    /// Fetches every playlist, unless a limit is given
+    pub async fn playlists(
+        &mut self,
+        limit: Option<u32>,
+        offset: Option<u32>,
+    ) -> Result<Vec<Box<dyn Playable + 'static>>> {
+        const PAGE_SIZE: u32 = 50;
+
+        let mut playables: Vec<Box<dyn Playable>> = Vec::new();
+        let mut fetched = 0;
+
+        // Libraries may have more playlists than fit into a single page
+        loop {
+            let page_limit = match limit {
+                Some(limit) => limit.saturating_sub(fetched).min(PAGE_SIZE),
+                None => PAGE_SIZE,
+            };
+
+            if page_limit == 0 {
+                break;
+            }
+
+            let page = self
+                .with_retry(|| {
+                    self.client.current_user_playlists_manual(
+                        Some(page_limit),
+                        Some(offset.unwrap_or(0) + fetched),
+                    )
+                })
+                .await
+                .context("Failed getting users playlists")?;
+
+            fetched += page.items.len() as u32;
+            playables.extend(
+                page.items
+                    .into_iter()
+                    .map(|item| Box::new(item) as Box<dyn Playable>),
+            );
+
+            if page.next.is_none() {
+                break;
+            }
+        }
+
+        Ok(playables)
+    }
+
+    /// Get the tracks the user has recently played, most recent first
+    ///
+    /// Only tracks played after the given time are included, if any
+    pub async fn recently_played(
+        &mut self,
+        limit: u32,
+        since: Option<DateTime<Utc>>,
+    ) -> Result<Vec<Track>> {
+        let Some(since) = since else {
+            let history = self
+                .with_retry(|| self.client.current_user_recently_played(Some(limit), None))
+                .await
+                .context("Failed getting recently played tracks")?
+                .items;
+
+            return Ok(history.into_iter().map(|i| Track::from(i.track)).collect());
+        };
+
+        // Spotify returns the tracks played right after the cursor, so newer tracks are reached
+        // by moving the cursor forward until a page isn't full anymore
+        let mut history = Vec::new();
+        let mut after = since;
+
+        loop {
+            let page = self
+                .with_retry(|| {
+                    self.client.current_user_recently_played(
+                        Some(RECENTLY_PLAYED_PAGE_SIZE),
+                        Some(TimeLimits::After(after)),
+                    )
+                })
+                .await
+                .context("Failed getting recently played tracks")?;
+
+            let full = page.items.len() == RECENTLY_PLAYED_PAGE_SIZE as usize;
+            history.extend(page.items);
+
+            // The next cursor is the time the newest track of this page was played at, in
+            // milliseconds
+            let next = page
+                .cursors
+                .and_then(|cursor| cursor.after)
+                .and_then(|after| after.parse::<i64>().ok())
+                .and_then(DateTime::from_timestamp_millis);
+
+            match next {
+                Some(next) if full && next > after => after = next,
+                _ => break,
+            }
+        }
+
+        history.sort_by_key(|item| std::cmp::Reverse(item.played_at));
+        history.truncate(limit as usize);
+
+        Ok(history.into_iter().map(|i| Track::from(i.track)).collect())
+    }
+
+    /// Get the users top tracks over the given time range
+    pub async fn top_tracks(
+        &mut self,
+        time_range: TimeRange,
+        limit: u32,
+    ) -> Result<Vec<Box<dyn Playable + 'static>>> {
+        let tracks = self
+            .with_retry(|| {
+                self.client
+                    .current_user_top_tracks_manual(Some(time_range), Some(limit), None)
+            })
+            .await
+            .context("Failed getting top tracks")?
+            .items;
+
+        let playables = tracks
+            .into_iter()
+            .map(|item| Box::new(item) as Box<dyn Playable>)
+            .collect();
+
+        Ok(playables)
+    }
+
+    /// Get the users top artists over the given time range
+    pub async fn top_artists(
+        &mut self,
+        time_range: TimeRange,
+        limit: u32,
+    ) -> Result<Vec<Box<dyn Playable + 'static>>> {
+        let artists = self
+            .with_retry(|| {
+                self.client
+                    .current_user_top_artists_manual(Some(time_range), Some(limit), None)
+            })
+            .await
+            .context("Failed getting top artists")?
+            .items;
+
+        let playables = artists
+            .into_iter()
+            .map(|item| Box::new(item) as Box<dyn Playable>)
+            .collect();
+
+        Ok(playables)
+    }
+
+    /// Start playing recommendations seeded from the currently playing track
+    pub async fn radio_from_current(&mut self, limit: u32) -> Result<()> {
+        let track = self.current_track().await?.ok_or(Error::NothingPlaying)?;
+
+        let seed = match track.id {
+            Some(PlayableId::Track(id)) => id,
+            _ => return Err(anyhow!("A radio can only be started from a track")),
+        };
+
+        let ids: Vec<PlayableId> = self
+            .recommendations(vec![seed], limit)
+            .await?
+            .into_iter()
+            .filter_map(|track| track.id)
+            .map(PlayableId::from)
+            .collect();
 
-        match current_playback.is_playing {
-            true => self.client.pause_playback(None).await,
-            false => self.client.resume_playback(None, None).await,
+        if ids.is_empty() {
+            return Err(anyhow!("No recommendations found for the current track"));
         }
-        .context("Failed toggling playback")?;
-
-        Ok(())
-    }
 
-    /// Get the current volume in percent
-    pub async fn volume_get(&mut self) -> Result<u8> {
-        self.ensure_device().await?;
+        if self.skip_dry_run(&format!("play {} recommended tracks", ids.len())) {
+            return Ok(());
+        }
 
-        let current_playback = self.playback_context().await?;
+        self.with_retry(|| {
+            self.client
+                .start_uris_playback(ids.clone(), None, None, None)
+        })
+        .await
+        .context("Failed playing recommendations")?;
 
-        Ok(current_playback
-            .device
-            .volume_percent
-            .context("No current volume")? as u8)
+        Ok(())
     }
 
-    /// Set the current volume in percent
-    pub async fn volume_set(&mut self, volume: u8) -> Result<()> {
-        self.ensure_device().await?;
-
-        self.client
-            .volume(volume.clamp(0, 100), None)
+    /// Get tracks recommended based on the seed tracks, spotify accepts up to 5 seeds
+    pub async fn recommendations(
+        &mut self,
+        seeds: Vec<TrackId<'static>>,
+        limit: u32,
+    ) -> Result<Vec<SimplifiedTrack>> {
+        let recommendations = self
+            .with_retry(|| {
+                self.client.recommendations(
+                    Vec::<RecommendationsAttribute>::new(),
+                    None::<Vec<ArtistId>>,
+                    None::<Vec<&str>>,
+                    Some(seeds.clone()),
+                    None,
+                    Some(limit),
+                )
+            })
             .await
-            .context("Failed setting volume")?;
+            .context("Failed getting recommendations")?;
 
-        Ok(())
+        Ok(recommendations.tracks)
     }
 
-    /// Increase volume by given percentage
-    pub async fn volume_up(&mut self, up: u8) -> Result<()> {
-        self.ensure_device().await?;
+    /// Get the IDs of the current and the most recently played tracks, most recent first
+    pub async fn recent_track_ids(&mut self, limit: u32) -> Result<Vec<TrackId<'static>>> {
+        let current = self.status().await?.and_then(|status| status.track.id);
 
-        let volume = self.volume_get().await?;
+        // The history only contains finished tracks, so the current track comes first
+        let mut ids: Vec<TrackId<'static>> = Vec::new();
+        let recent = self.recently_played(limit, None).await?;
 
-        self.volume_set(volume + up.min(100 - volume)).await?;
+        for id in current
+            .into_iter()
+            .chain(recent.into_iter().filter_map(|t| t.id))
+        {
+            if let PlayableId::Track(id) = id {
+                if !ids.contains(&id) {
+                    ids.push(id);
+                }
+            }
+        }
 
-        Ok(())
+        ids.truncate(limit as usize);
+
+        Ok(ids)
     }
 
-    /// Decrease volume by given percentage
-    pub async fn volume_down(&mut self, down: u8) -> Result<()> {
-        self.ensure_device().await?;
+    /// Get the amount of items coming up after the current item
+    ///
+    /// Spotify includes the remaining items of the playback context, not only queued items
+    pub async fn queue_length(&mut self) -> Result<usize> {
+        let queue = self
+            .with_retry(|| self.client.current_user_queue())
+            .await
+            .context("Failed getting the playback queue")?;
 
-        let volume = self.volume_get().await?;
+        Ok(queue.queue.len())
+    }
 
-        self.volume_set(volume - down.min(volume)).await?;
+    /// Get the current item and the items coming up after it
+    ///
+    /// Spotify only returns the next few items, including the remaining items of the context
+    pub async fn queue(&mut self) -> Result<(Option<Track>, Vec<Track>)> {
+        let queue = self
+            .with_retry(|| self.client.current_user_queue())
+            .await
+            .context("Failed getting the playback queue")?;
 
-        Ok(())
+        Ok((
+            queue.currently_playing.map(Track::from),
+            queue.queue.into_iter().map(Track::from).collect(),
+        ))
     }
 
-    /// Search for content by using a search query and specifying the search type
-    pub async fn search(
-        &mut self,
-        query: String,
-        search_type: SearchType,
-        limit: Option<u32>,
-    ) -> Result<Vec<Box<dyn Playable + 'static>>> {
-        self.ensure_device().await?;
-
-        let search = self
-            .client
-            .search(&query, search_type, None, None, limit, None)
-            .await
-            .context("Failed searching content")?;
+    /// Save the current item and the queue to a new private playlist
+    ///
+    /// Items without an ID, e.g. local files, are skipped. Returns the playlist and how many items
+    /// were saved, or Ok(None) if this is a dry run
+    pub async fn save_queue(&mut self, name: &str) -> Result<Option<(FullPlaylist, usize)>> {
+        let (current, upcoming) = self.queue().await?;
 
-        let mut results: Vec<Box<dyn Playable>> = Vec::new();
+        let ids: Vec<PlayableId> = current
+            .into_iter()
+            .chain(upcoming)
+            .filter_map(|item| item.id)
+            .collect();
 
-        fn map_playable<T: Playable + 'static>(items: Vec<T>) -> Vec<Box<dyn Playable>> {
-            items
-                .into_iter()
-                .map(|item| Box::new(item) as Box<dyn Playable>)
-                .collect()
+        if ids.is_empty() {
+            return Err(anyhow!("The queue is empty, there is nothing to save"));
         }
 
-        match search {
-            SearchResult::Playlists(playlists) => results.extend(map_playable(playlists.items)),
-            SearchResult::Albums(albums) => results.extend(map_playable(albums.items)),
-            SearchResult::Artists(artists) => results.extend(map_playable(artists.items)),
-            SearchResult::Tracks(tracks) => results.extend(map_playable(tracks.items)),
-            SearchResult::Shows(shows) => results.extend(map_playable(shows.items)),
-            SearchResult::Episodes(episodes) => results.extend(map_playable(episodes.items)),
+        let Some(playlist) = self.create_playlist(name, false, None).await? else {
+            println!("Would add {} items to the playlist", ids.len());
+            return Ok(None);
+        };
+
+        // Spotify accepts at most 100 items per request
+        for chunk in ids.chunks(PLAYLIST_ADD_LIMIT) {
+            self.with_retry(|| {
+                self.client
+                    .playlist_add_items(playlist.id.clone(), chunk.to_vec(), None)
+            })
+            .await
+            .context("Failed adding the queue to the playlist")?;
         }
 
-        Ok(results)
+        Ok(Some((playlist, ids.len())))
     }
 
-    /// Play a Playable item using the client
-    pub async fn play(&mut self, item: &Box<dyn Playable>) -> Result<()> {
-        self.ensure_device().await?;
+    /// Set the current playback device
+    ///
+    /// Playback starts on the device if play is set, otherwise it keeps its playback state
+    pub async fn set_device(&mut self, device: Device, play: bool) -> Result<()> {
+        let device_id = device.id.clone().context("Playback device is missing ID")?;
+
+        log::debug!(
+            "Transferring playback to device \"{}\" ({})",
+            device.name,
+            device_id
+        );
+
+        if self.skip_dry_run(&format!("transfer playback to \"{}\"", device.name)) {
+            // Pretend the device was selected, so it isn't selected again by every command
+            self.cached_device = Some(CachedDevice::new(device));
+            return Ok(());
+        }
 
-        item.play(&self.client)
+        self.with_retry(|| {
+            self.client
+                .transfer_playback(&device_id, play.then_some(true))
+        })
+        .await
+        .context("Failed setting playback device")?;
+
+        // Unfortunately the spotify API does not tell us, when the device has finished updating
+        if !self
+            .wait_for(DEVICE_WAIT_TIME, |p| p.device.name == device.name)
             .await
-            .context("Failed playing item")?;
+        {
+            return Err(anyhow!("Timed out while setting a playback device"));
+        }
+
+        save_last_device(&device_id);
+        self.cached_device = Some(CachedDevice::new(device));
 
         Ok(())
     }
 
-    /// Get all playlists in users library
-    pub async fn playlists(&mut self) -> Result<Vec<Box<dyn Playable + 'static>>> {
-        let playlists = self
-            .client
-            .current_user_playlists_manual(None, None)
+    /// Resume the playback after switching devices
+    ///
+    /// If nothing is left to resume on the device, the context played last is started instead.
+    /// Returns false if there is nothing to resume at all
+    pub async fn resume_on_device(&mut self) -> Result<bool> {
+        let current_playback = self
+            .with_retry(|| {
+                self.client
+                    .current_playback(None, None::<Option<&AdditionalType>>)
+            })
             .await
-            .context("Failed getting users playlists")?
-            .items;
+            .context("Failed determining current playback state")?;
 
-        let playables = playlists
-            .into_iter()
-            .map(|item| Box::new(item) as Box<dyn Playable>)
-            .collect();
+        if current_playback.is_some_and(|p| p.item.is_some()) {
+            self.playback_resume().await?;
+            return Ok(true);
+        }
 
-        Ok(playables)
+        let Some(context) = load_last_context().and_then(|uri| context_id(&uri)) else {
+            return Ok(false);
+        };
+
+        if self.skip_dry_run(&format!("resume {}", context.uri())) {
+            return Ok(true);
+        }
+
+        self.with_retry(|| {
+            self.client
+                .start_context_playback(context.clone(), None, None, None)
+        })
+        .await
+        .context("Failed resuming the last played context")?;
+
+        Ok(true)
     }
 
-    /// Set the current playback device
-    pub async fn set_device(&mut self, device: Device) -> Result<()> {
-        self.client
-            .transfer_playback(
-                device
-                    .id
-                    .clone()
-                    .context("Playback device is missing ID")?
-                    .as_str(),
-                None,
-            )
+    /// Pause the playback and forget the current playback device
+    ///
+    /// The spotify API has no way of disconnecting a device, so the device stays available and
+    /// the next command selects a playback device again. Returns false if no device is active
+    pub async fn disconnect(&mut self) -> Result<bool> {
+        let playback_context = self
+            .with_retry(|| {
+                self.client
+                    .current_playback(None, None::<Option<&AdditionalType>>)
+            })
             .await
-            .context("Failed setting playback device")?;
+            .context("Failed determining current playback state")?;
 
-        // Unfortunately the spotify API does not tell us,
-        // when the device has finished updating. That means, we have to poll
-        // for device changes
+        let current_playback = match playback_context {
+            Some(current_playback) => current_playback,
+            None => return Ok(false),
+        };
 
-        const MAX_WAIT_TIME: Duration = Duration::from_secs(1);
-        const POLL_INTERVAL: Duration = Duration::from_millis(100);
-        let start_time = Instant::now();
+        if current_playback.is_playing && !self.skip_dry_run("pause playback") {
+            self.with_retry(|| self.client.pause_playback(None))
+                .await
+                .context("Failed pausing playback")?;
+        }
 
-        loop {
-            if start_time.elapsed() < MAX_WAIT_TIME {
-                if let Ok(Some(current_playback)) = self
-                    .client
-                    .current_playback(None, None::<Option<&AdditionalType>>)
-                    .await
-                {
-                    if current_playback.device.name == device.name {
-                        self.cached_device = Some(CachedDevice::new(device));
-                        break;
-                    }
-                }
+        self.cached_device = None;
 
-                tokio::time::sleep(POLL_INTERVAL).await;
-            } else {
-                return Err(anyhow!("Timed out while setting a playback device"));
-            }
+        // Otherwise the next command would silently reuse the device used last time
+        if !self.dry_run {
+            forget_last_device();
         }
 
-        Ok(())
+        Ok(true)
     }
 
     /// Get all available playback devices
     pub async fn devices(&self) -> Result<Vec<Device>> {
         let devices = self
-            .client
-            .device()
+            .with_retry(|| self.client.device())
             .await
             .context("Failed getting available playback devices")?;
 
@@ -297,8 +1749,11 @@ impl SpotifyPlayer {
     pub async fn track_next(&mut self) -> Result<()> {
         self.ensure_device().await?;
 
-        self.client
-            .next_track(None)
+        if self.skip_dry_run("skip to the next track") {
+            return Ok(());
+        }
+
+        self.with_retry(|| self.client.next_track(None))
             .await
             .context("Failed skipping track")?;
 
@@ -306,23 +1761,49 @@ impl SpotifyPlayer {
     }
 
     /// Go to previous track
-    pub async fn track_prev(&mut self) -> Result<()> {
+    ///
+    /// If smart is set or smart previous is configured, the current track is restarted instead
+    /// once it has played for a few seconds, like the previous button of the Spotify app does.
+    /// Returns false if the current track was restarted
+    pub async fn track_prev(&mut self, smart: bool) -> Result<bool> {
         self.ensure_device().await?;
 
-        self.client
-            .previous_track(None)
+        if smart || self.smart_prev {
+            let progress = self.playback_context().await?.progress;
+
+            if progress.is_some_and(|p| p > SMART_PREV_RESTART_THRESHOLD) {
+                self.seek(Duration::ZERO).await?;
+                return Ok(false);
+            }
+        }
+
+        if self.skip_dry_run("go to the previous track") {
+            return Ok(true);
+        }
+
+        self.with_retry(|| self.client.previous_track(None))
             .await
             .context("Failed skipping track")?;
 
-        Ok(())
+        Ok(true)
+    }
+
+    /// Get whether shuffle is currently on
+    ///
+    /// Only reads the playback state, so the user is never asked to select a device
+    pub async fn shuffle_state(&mut self) -> Result<bool> {
+        Ok(self.active_playback().await?.shuffle_state)
     }
 
     /// Set shuffle mode to on
     pub async fn shuffle_on(&mut self) -> Result<()> {
         self.ensure_device().await?;
 
-        self.client
-            .shuffle(true, None)
+        if self.skip_dry_run("turn shuffle on") {
+            return Ok(());
+        }
+
+        self.with_retry(|| self.client.shuffle(true, None))
             .await
             .context("Failed turning shuffle on")?;
 
@@ -333,8 +1814,11 @@ impl SpotifyPlayer {
     pub async fn shuffle_off(&mut self) -> Result<()> {
         self.ensure_device().await?;
 
-        self.client
-            .shuffle(false, None)
+        if self.skip_dry_run("turn shuffle off") {
+            return Ok(());
+        }
+
+        self.with_retry(|| self.client.shuffle(false, None))
             .await
             .context("Failed turning shuffle off")?;
 
@@ -356,12 +1840,22 @@ impl SpotifyPlayer {
         Ok(())
     }
 
+    /// Get the current repeat mode
+    ///
+    /// Only reads the playback state, so the user is never asked to select a device
+    pub async fn repeat_state(&mut self) -> Result<RepeatState> {
+        Ok(self.active_playback().await?.repeat_state)
+    }
+
     /// Set repeat mode to on
     pub async fn repeat_on(&mut self) -> Result<()> {
         self.ensure_device().await?;
 
-        self.client
-            .repeat(RepeatState::Context, None)
+        if self.skip_dry_run("turn repeat on") {
+            return Ok(());
+        }
+
+        self.with_retry(|| self.client.repeat(RepeatState::Context, None))
             .await
             .context("Failed turning shuffle on")?;
 
@@ -372,8 +1866,11 @@ impl SpotifyPlayer {
     pub async fn repeat_off(&mut self) -> Result<()> {
         self.ensure_device().await?;
 
-        self.client
-            .repeat(RepeatState::Off, None)
+        if self.skip_dry_run("turn repeat off") {
+            return Ok(());
+        }
+
+        self.with_retry(|| self.client.repeat(RepeatState::Off, None))
             .await
             .context("Failed turning shuffle off")?;
 
@@ -384,8 +1881,11 @@ impl SpotifyPlayer {
     pub async fn repeat_track(&mut self) -> Result<()> {
         self.ensure_device().await?;
 
-        self.client
-            .repeat(RepeatState::Track, None)
+        if self.skip_dry_run("repeat the current track") {
+            return Ok(());
+        }
+
+        self.with_retry(|| self.client.repeat(RepeatState::Track, None))
             .await
             .context("Failed turning shuffle off")?;
 
@@ -407,46 +1907,467 @@ impl SpotifyPlayer {
         Ok(())
     }
 
+    /// Print the action which would be taken instead of taking it, if this is a dry run
+    ///
+    /// Returns true if the action should be skipped
+    fn skip_dry_run(&self, action: &str) -> bool {
+        if self.dry_run {
+            println!("Would {}", action);
+        }
+
+        self.dry_run
+    }
+
+    /// Run an API request, retrying it with backoff in case it is rate limited by spotify
+    ///
+    /// Every attempt fails if it takes longer than the request timeout. Playback requests which
+    /// were forbidden are explained, if the account lacks Spotify Premium
+    async fn with_retry<T, E, F, Fut>(&self, request: F) -> Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = std::result::Result<T, E>>,
+        E: Into<anyhow::Error>,
+    {
+        match self.retry(request).await {
+            Ok(value) => Ok(value),
+            Err(error) => Err(self.explain_premium(error).await),
+        }
+    }
+
+    /// Run an API request, retrying it with backoff in case it is rate limited by spotify
+    ///
+    /// Every attempt fails if it takes longer than the request timeout
+    async fn retry<T, E, F, Fut>(&self, request: F) -> Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = std::result::Result<T, E>>,
+        E: Into<anyhow::Error>,
+    {
+        let mut attempt = 0;
+        let mut waited = Duration::ZERO;
+
+        loop {
+            let error = match tokio::time::timeout(self.request_timeout, request()).await {
+                Ok(Ok(value)) => {
+                    if attempt > 0 {
+                        log::info!(
+                            "Request succeeded after {} retries, waited {:?} in total",
+                            attempt,
+                            waited
+                        );
+                    }
+
+                    return Ok(value);
+                }
+                Ok(Err(e)) => e.into(),
+                Err(_) => {
+                    return Err(anyhow!(
+                        "Request timed out after {}s",
+                        self.request_timeout.as_secs_f64()
+                    ))
+                }
+            };
+
+            // The underlying error is hard to read, but still useful when debugging
+            if is_connection_error(&error) {
+                log::info!("Request failed to connect: {:?}", error);
+                return Err(Error::Offline.into());
+            }
+
+            let Some(limit) = rate_limit(&error, attempt) else {
+                return Err(error);
+            };
+
+            // Shows how aggressively e.g. a status bar may poll before being rate limited
+            log::info!(
+                "Request was rate limited (429), Retry-After: {}",
+                match limit.retry_after {
+                    Some(retry_after) => format!("{}s", retry_after.as_secs()),
+                    None => "not sent".to_string(),
+                }
+            );
+
+            match limit.delay {
+                Some(delay) if attempt < self.max_retries => {
+                    log::info!(
+                        "Retrying in {:?} (attempt {} of {})",
+                        delay,
+                        attempt + 1,
+                        self.max_retries
+                    );
+
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                    waited += delay;
+                }
+                Some(_) => {
+                    log::info!(
+                        "Giving up after {} retries, waited {:?} in total",
+                        attempt,
+                        waited
+                    );
+                    return Err(error);
+                }
+                None => {
+                    log::info!(
+                        "Not retrying, since waiting is longer than {:?}",
+                        RATE_LIMIT_MAX_DELAY
+                    );
+                    return Err(error);
+                }
+            }
+        }
+    }
+
+    /// Explain a failed request, if it was forbidden since the account lacks Spotify Premium
+    ///
+    /// Spotify only allows controlling playback with a Premium account, the reason a request was
+    /// forbidden is not available to us though, so the subscription level is checked afterwards.
+    /// Other requests may be forbidden for other reasons, e.g. missing scopes
+    async fn explain_premium(&self, error: anyhow::Error) -> anyhow::Error {
+        if !is_forbidden_playback(&error) {
+            return error;
+        }
+
+        match self.current_user().await {
+            Ok(user) if user.product == Some(SubscriptionLevel::Free) => {
+                error.context("Controlling playback requires a Spotify Premium account")
+            }
+            _ => error,
+        }
+    }
+
+    /// Poll the current playback until the predicate holds or the timeout elapses
+    ///
+    /// Spotify applies most changes asynchronously without telling us when they are done, so we
+    /// have to poll for them. Returns false if the predicate didn't hold before timing out
+    async fn wait_for<P>(&self, timeout: Duration, predicate: P) -> bool
+    where
+        P: Fn(&CurrentPlaybackContext) -> bool,
+    {
+        // Nothing was changed during a dry run, so there is nothing to wait for
+        if self.dry_run {
+            return true;
+        }
+
+        let start_time = Instant::now();
+
+        while start_time.elapsed() < timeout {
+            let request = self
+                .client
+                .current_playback(None, None::<Option<&AdditionalType>>);
+
+            if let Ok(Ok(Some(current_playback))) =
+                tokio::time::timeout(self.request_timeout, request).await
+            {
+                if predicate(&current_playback) {
+                    return true;
+                }
+            }
+
+            tokio::time::sleep(WAIT_POLL_INTERVAL).await;
+        }
+
+        false
+    }
+
     /// Get the current playback context
     async fn playback_context(&mut self) -> Result<CurrentPlaybackContext> {
         let current_playback = self
-            .client
-            .current_playback(None, None::<Option<&AdditionalType>>)
+            .with_retry(|| {
+                self.client
+                    .current_playback(None, None::<Option<&AdditionalType>>)
+            })
+            .await
+            .context("Failed determining current playback state")?
+            .ok_or(Error::NoDevice)?;
+
+        Ok(current_playback)
+    }
+
+    /// Get the current playback context, without selecting a playback device
+    ///
+    /// Fails with NothingPlaying if no device is active
+    async fn active_playback(&mut self) -> Result<CurrentPlaybackContext> {
+        let current_playback = self
+            .with_retry(|| {
+                self.client
+                    .current_playback(None, None::<Option<&AdditionalType>>)
+            })
             .await
             .context("Failed determining current playback state")?
-            .context("No current playback device")?;
+            .ok_or(Error::NothingPlaying)?;
 
         Ok(current_playback)
     }
 
-    /// Ensure that there is an active playback device
-    async fn ensure_device(&mut self) -> Result<()> {
+    /// Check whether there is an active playback device, without selecting one
+    ///
+    /// Used by commands which only read information, so they never ask the user to select a
+    /// device. An active device is cached, so later commands don't have to check again
+    async fn has_active_device(&mut self) -> Result<bool> {
         if let Some(cached) = &self.cached_device {
             if cached.is_valid() {
-                return Ok(());
+                log::debug!("Using cached playback device");
+                return Ok(true);
             }
         }
 
+        log::debug!("Playback device cache missed, checking the active device");
+
         let playback_context = self
-            .client
-            .current_playback(None, None::<Option<&AdditionalType>>)
+            .with_retry(|| {
+                self.client
+                    .current_playback(None, None::<Option<&AdditionalType>>)
+            })
             .await
             .context("Failed determining current playback state")?;
 
-        if let Some(current_playback) = playback_context {
-            self.cached_device = Some(CachedDevice::new(current_playback.device));
+        match playback_context {
+            Some(current_playback) => {
+                log::info!(
+                    "Using active playback device \"{}\"",
+                    current_playback.device.name
+                );
+                self.cached_device = Some(CachedDevice::new(current_playback.device));
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Ensure that there is an active playback device, selecting one if there is none
+    pub async fn ensure_device(&mut self) -> Result<()> {
+        if self.has_active_device().await? {
             return Ok(());
         }
 
         let devices = self.devices().await?;
 
+        // Prefer the configured default device, as long as it is available
+        if let Some(default_device) = &self.default_device {
+            let device = devices.iter().find(|d| {
+                d.id.as_deref() == Some(default_device.as_str())
+                    || d.name.eq_ignore_ascii_case(default_device)
+            });
+
+            match device {
+                Some(device) => {
+                    log::info!("Using default playback device \"{}\"", device.name);
+                    return self.set_device(device.clone(), false).await;
+                }
+                None => eprintln!(
+                    "Default device \"{}\" is not available, selecting another device",
+                    default_device
+                ),
+            }
+        }
+
+        // Prefer the device used last time, as long as it is still available
+        if let Some(id) = load_last_device() {
+            if let Some(device) = devices.iter().find(|d| d.id.as_ref() == Some(&id)) {
+                log::info!("Using last playback device \"{}\"", device.name);
+                return self.set_device(device.clone(), false).await;
+            }
+        }
+
         let device = match devices.len() {
+            0 => return Err(Error::NoDevice.into()),
             1 => devices.into_iter().next().unwrap(),
             _ => ui::select_device(devices)?,
         };
 
-        self.set_device(device).await?;
+        log::info!("Using playback device \"{}\"", device.name);
+        self.set_device(device, false).await?;
 
         Ok(())
     }
 }
+
+/// Get the ID of a context spotify can start playing from its URI, if it is one
+fn context_id(uri: &str) -> Option<PlayContextId<'static>> {
+    // Only the ID type matching the type in the URI parses it
+    if let Ok(id) = AlbumId::from_uri(uri) {
+        return Some(id.into_static().into());
+    }
+
+    if let Ok(id) = ArtistId::from_uri(uri) {
+        return Some(id.into_static().into());
+    }
+
+    if let Ok(id) = PlaylistId::from_uri(uri) {
+        return Some(id.into_static().into());
+    }
+
+    ShowId::from_uri(uri).ok().map(|id| id.into_static().into())
+}
+
+/// Collapse tracks with the same name and primary artist into the most popular one
+///
+/// Popular tracks are often re-released on several albums, which clutters search results. The
+/// most popular track takes the place of the first of its duplicates, so the order is kept
+fn dedup_tracks(tracks: Vec<FullTrack>) -> Vec<FullTrack> {
+    let mut deduped: Vec<FullTrack> = Vec::new();
+    let mut positions: HashMap<(String, Option<String>), usize> = HashMap::new();
+
+    for track in tracks {
+        let key = (
+            track.name.to_lowercase(),
+            track.artists.first().map(|a| a.name.to_lowercase()),
+        );
+
+        match positions.get(&key) {
+            Some(&i) if track.popularity > deduped[i].popularity => deduped[i] = track,
+            Some(_) => {}
+            None => {
+                positions.insert(key, deduped.len());
+                deduped.push(track);
+            }
+        }
+    }
+
+    deduped
+}
+
+/// The last used playback device, persisted across runs
+#[derive(Serialize, Deserialize)]
+struct LastDevice {
+    id: String,
+}
+
+/// Get the last used playback device storage path
+fn last_device_path() -> PathBuf {
+    let mut last_device_path = auth::data_dir();
+    last_device_path.push("last_device.json");
+    last_device_path
+}
+
+/// Load the ID of the last used playback device, if any was saved
+fn load_last_device() -> Option<String> {
+    let last_device_str = fs::read_to_string(last_device_path()).ok()?;
+
+    serde_json::from_str::<LastDevice>(&last_device_str)
+        .ok()
+        .map(|d| d.id)
+}
+
+/// Save the ID of the last used playback device
+///
+/// Remembering the device is only a convenience, so failing to save it is ignored
+fn save_last_device(id: &str) {
+    let last_device = LastDevice { id: id.to_string() };
+
+    if let (Ok(()), Ok(last_device_str)) = (auth::ensure_dir(), serde_json::to_string(&last_device))
+    {
+        let _ = fs::write(last_device_path(), last_device_str);
+    }
+}
+
+/// Forget the last used playback device
+///
+/// Like saving it, failing to delete it is ignored
+fn forget_last_device() {
+    let _ = fs::remove_file(last_device_path());
+}
+
+/// The context which was played last, persisted across runs
+#[derive(Serialize, Deserialize)]
+struct LastContext {
+    uri: String,
+}
+
+/// Get the last played context storage path
+fn last_context_path() -> PathBuf {
+    let mut last_context_path = auth::data_dir();
+    last_context_path.push("last_context.json");
+    last_context_path
+}
+
+/// Load the URI of the last played context, if any was saved
+fn load_last_context() -> Option<String> {
+    let last_context_str = fs::read_to_string(last_context_path()).ok()?;
+
+    serde_json::from_str::<LastContext>(&last_context_str)
+        .ok()
+        .map(|c| c.uri)
+}
+
+/// Save the URI of the last played context, if it can be played again
+///
+/// Remembering the context is only a convenience, so failing to save it is ignored
+fn save_last_context(uri: &str) {
+    // Following the playback sees the same context over and over, so it is only written once
+    if context_id(uri).is_none() || load_last_context().as_deref() == Some(uri) {
+        return;
+    }
+
+    let last_context = LastContext {
+        uri: uri.to_string(),
+    };
+
+    if let (Ok(()), Ok(last_context_str)) =
+        (auth::ensure_dir(), serde_json::to_string(&last_context))
+    {
+        let _ = fs::write(last_context_path(), last_context_str);
+    }
+}
+
+/// Check whether a playback request, i.e. one to /me/player, was forbidden
+fn is_forbidden_playback(error: &anyhow::Error) -> bool {
+    if let Some(ClientError::Http(http_error)) = error.downcast_ref::<ClientError>() {
+        if let HttpError::StatusCode(response) = http_error.as_ref() {
+            return response.status().as_u16() == 403
+                && response.url().path().starts_with("/v1/me/player");
+        }
+    }
+
+    false
+}
+
+/// Check whether a request failed since spotify couldn't be reached, e.g. without internet
+fn is_connection_error(error: &anyhow::Error) -> bool {
+    if let Some(ClientError::Http(http_error)) = error.downcast_ref::<ClientError>() {
+        if let HttpError::Client(client_error) = http_error.as_ref() {
+            return client_error.is_connect();
+        }
+    }
+
+    false
+}
+
+/// A request which failed because of rate limiting
+struct RateLimit {
+    /// The Retry-After header, if spotify sent one
+    retry_after: Option<Duration>,
+    /// How long to wait before retrying, none if it is too long to wait for
+    delay: Option<Duration>,
+}
+
+/// Check whether a request failed because of rate limiting and how long to wait before retrying
+///
+/// Uses the Retry-After header if spotify sent one, otherwise backs off exponentially
+fn rate_limit(error: &anyhow::Error, attempt: u32) -> Option<RateLimit> {
+    if let Some(ClientError::Http(http_error)) = error.downcast_ref::<ClientError>() {
+        if let HttpError::StatusCode(response) = http_error.as_ref() {
+            if response.status().as_u16() != 429 {
+                return None;
+            }
+
+            let retry_after = response
+                .headers()
+                .get("retry-after")
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<u64>().ok())
+                .map(Duration::from_secs);
+            let delay = retry_after
+                .unwrap_or(RATE_LIMIT_BASE_DELAY.saturating_mul(2u32.saturating_pow(attempt)));
+
+            return Some(RateLimit {
+                retry_after,
+                delay: (delay <= RATE_LIMIT_MAX_DELAY).then_some(delay),
+            });
+        }
+    }
+
+    None
+}
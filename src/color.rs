@@ -0,0 +1,50 @@
+use std::{
+    io::IsTerminal,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use owo_colors::OwoColorize;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Decide whether output should be colorized
+///
+/// Colors are disabled if requested using the flag, if the NO_COLOR environment variable is set
+/// or if stdout is not a terminal (e.g. when piping)
+pub fn init(no_color: bool) {
+    let no_color_env = matches!(std::env::var_os("NO_COLOR"), Some(v) if !v.is_empty());
+
+    ENABLED.store(
+        !no_color && !no_color_env && std::io::stdout().is_terminal(),
+        Ordering::Relaxed,
+    );
+}
+
+/// Is colorized output enabled
+fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Colorize a track, playlist or other content name
+pub fn name(text: &str) -> String {
+    match enabled() {
+        true => text.bold().to_string(),
+        false => text.to_string(),
+    }
+}
+
+/// Colorize a list of artists
+pub fn artists(text: &str) -> String {
+    match enabled() {
+        true => text.green().to_string(),
+        false => text.to_string(),
+    }
+}
+
+/// Colorize a content type label
+pub fn label(text: &str) -> String {
+    match enabled() {
+        true => text.cyan().to_string(),
+        false => text.to_string(),
+    }
+}
@@ -0,0 +1,230 @@
+use anyhow::{Context, Result};
+use rspotify::model::{Country, Market};
+use serde::Deserialize;
+use std::{fs, path::PathBuf, time::Duration};
+
+use crate::{auth, client::ToggleFallback};
+
+const DEFAULT_MAX_RETRIES: u32 = 3;
+
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+// Polling more often than this quickly gets requests rate limited by spotify
+const MIN_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+const DEFAULT_VOLUME_STEP: u8 = 5;
+
+const DEFAULT_AUTOPLAY_THRESHOLD: u32 = 3;
+
+const DEFAULT_AUTOPLAY_SEEDS: u32 = 5;
+
+const DEFAULT_SEARCH_CACHE_TTL: Duration = Duration::from_secs(60);
+
+const DEFAULT_LYRICS_PROVIDER: &str = "https://lrclib.net/api/get";
+
+/// User configuration, loaded from the config file in the data directory
+///
+/// All fields are optional, missing fields fall back to their defaults
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Config {
+    /// The default market used when searching, as an ISO 3166-1 alpha-2 country code
+    market: Option<String>,
+    /// How often rate limited requests are retried
+    max_retries: Option<u32>,
+    /// The name or ID of the playback device used if no device is active
+    default_device: Option<String>,
+    /// The URL of the lrclib compatible endpoint lyrics are fetched from
+    lyrics_provider: Option<String>,
+    /// How often features which watch the playback poll spotify, in seconds
+    poll_interval: Option<f64>,
+    /// Whether explicit results are included when searching with --clean only finds explicit results
+    clean_fallback: Option<bool>,
+    /// Whether going to the previous track restarts the current track if it played for a while
+    smart_prev: Option<bool>,
+    /// What toggling starts playing, if nothing has played on the device yet
+    toggle_fallback: Option<ToggleFallback>,
+    /// How many percentage points a bare + or - changes the volume by
+    volume_step: Option<u8>,
+    /// Autoplay queues recommendations once fewer items than this are coming up
+    autoplay_threshold: Option<u32>,
+    /// How many recently played tracks autoplay bases recommendations on
+    autoplay_seeds: Option<u32>,
+    /// How long search results are cached, in seconds
+    search_cache_ttl: Option<u64>,
+    /// How long a single request may take before it fails, in seconds
+    timeout: Option<f64>,
+    /// The HTTP or HTTPS proxy all requests are sent through
+    proxy: Option<String>,
+}
+
+impl Config {
+    /// Get the configured default market, if any
+    pub fn market(&self) -> Result<Option<Market>> {
+        self.market
+            .as_deref()
+            .map(parse_market)
+            .transpose()
+            .map_err(anyhow::Error::msg)
+            .context("Invalid market in config file")
+    }
+
+    /// Get how often rate limited requests are retried
+    pub fn max_retries(&self) -> u32 {
+        self.max_retries.unwrap_or(DEFAULT_MAX_RETRIES)
+    }
+
+    /// Get the name or ID of the configured default playback device, if any
+    pub fn default_device(&self) -> Option<&str> {
+        self.default_device.as_deref()
+    }
+
+    /// Get how often features which watch the playback poll spotify
+    pub fn poll_interval(&self) -> Duration {
+        self.poll_interval
+            .map(clamp_poll_interval)
+            .unwrap_or(DEFAULT_POLL_INTERVAL)
+    }
+
+    /// Get whether explicit results are included, if searching with --clean only finds explicit
+    /// results
+    pub fn clean_fallback(&self) -> bool {
+        self.clean_fallback.unwrap_or(false)
+    }
+
+    /// Get whether going to the previous track restarts the current track if it played for a while
+    pub fn smart_prev(&self) -> bool {
+        self.smart_prev.unwrap_or(false)
+    }
+
+    /// Get what toggling starts playing, if nothing has played on the device yet
+    pub fn toggle_fallback(&self) -> ToggleFallback {
+        self.toggle_fallback.unwrap_or_default()
+    }
+
+    /// Get how many percentage points a bare + or - changes the volume by
+    pub fn volume_step(&self) -> u8 {
+        self.volume_step.unwrap_or(DEFAULT_VOLUME_STEP).min(100)
+    }
+
+    /// Get the amount of upcoming items below which autoplay queues recommendations
+    pub fn autoplay_threshold(&self) -> u32 {
+        self.autoplay_threshold
+            .unwrap_or(DEFAULT_AUTOPLAY_THRESHOLD)
+    }
+
+    /// Get how many recently played tracks autoplay bases recommendations on, spotify accepts at
+    /// most 5
+    pub fn autoplay_seeds(&self) -> u32 {
+        self.autoplay_seeds
+            .unwrap_or(DEFAULT_AUTOPLAY_SEEDS)
+            .clamp(1, 5)
+    }
+
+    /// Get how long search results are cached, zero if the search cache is disabled
+    pub fn search_cache_ttl(&self) -> Duration {
+        self.search_cache_ttl
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_SEARCH_CACHE_TTL)
+    }
+
+    /// Get how long a single request may take before it fails, if configured
+    pub fn timeout(&self) -> Result<Option<Duration>> {
+        self.timeout
+            .map(parse_timeout)
+            .transpose()
+            .map_err(anyhow::Error::msg)
+            .context("Invalid timeout in config file")
+    }
+
+    /// Get the HTTP or HTTPS proxy all requests are sent through, if configured
+    pub fn proxy(&self) -> Result<Option<&str>> {
+        if let Some(proxy) = &self.proxy {
+            reqwest::Proxy::all(proxy).context("Invalid proxy in config file")?;
+        }
+
+        Ok(self.proxy.as_deref())
+    }
+
+    /// Get the URL of the lyrics provider
+    pub fn lyrics_provider(&self) -> &str {
+        self.lyrics_provider
+            .as_deref()
+            .unwrap_or(DEFAULT_LYRICS_PROVIDER)
+    }
+}
+
+/// Get the config file path
+pub fn config_path() -> PathBuf {
+    let mut config_path = auth::data_dir();
+    config_path.push("config.json");
+    config_path
+}
+
+/// Load the config file, or the default config if there is none
+pub fn load() -> Result<Config> {
+    let path = config_path();
+
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+
+    let config_str = fs::read_to_string(path).context("Failed reading config file")?;
+
+    serde_json::from_str::<Config>(&config_str).context("Failed parsing config file")
+}
+
+/// Send all requests through the configured proxy, if any
+///
+/// rspotify builds its HTTP client itself, the only way to configure its proxy is using the
+/// standard proxy environment variables, which are read when a client is built. Setting them is
+/// only sound while no other threads run, so this has to be called before the async runtime is
+/// started. The configured proxy takes precedence over the environment
+pub fn apply_proxy() -> Result<()> {
+    if let Some(proxy) = load()?.proxy()? {
+        std::env::set_var("HTTP_PROXY", proxy);
+        std::env::set_var("HTTPS_PROXY", proxy);
+    }
+
+    Ok(())
+}
+
+/// Convert a poll interval in seconds, raising it to the minimum poll interval if it is shorter
+pub fn clamp_poll_interval(seconds: f64) -> Duration {
+    match Duration::try_from_secs_f64(seconds) {
+        Ok(interval) if interval >= MIN_POLL_INTERVAL => interval,
+        _ => {
+            eprintln!(
+                "Poll interval of {}s is too short, using {}s instead",
+                seconds,
+                MIN_POLL_INTERVAL.as_secs()
+            );
+            MIN_POLL_INTERVAL
+        }
+    }
+}
+
+/// Parse a request timeout in seconds, which has to be positive
+pub fn parse_timeout(seconds: f64) -> Result<Duration, String> {
+    match Duration::try_from_secs_f64(seconds) {
+        Ok(timeout) if !timeout.is_zero() => Ok(timeout),
+        _ => Err(format!(
+            "\"{}\" is not a positive amount of seconds",
+            seconds
+        )),
+    }
+}
+
+/// Parse a market from a two-letter uppercase country code
+pub fn parse_market(code: &str) -> Result<Market, String> {
+    if code.len() != 2 || !code.chars().all(|c| c.is_ascii_uppercase()) {
+        return Err(format!(
+            "\"{}\" is not a two-letter uppercase country code",
+            code
+        ));
+    }
+
+    serde_json::from_value::<Country>(serde_json::Value::String(code.to_string()))
+        .map(Market::Country)
+        .map_err(|_| format!("\"{}\" is not a known country code", code))
+}
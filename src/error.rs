@@ -0,0 +1,64 @@
+use std::fmt::{Display, Formatter};
+
+/// The exit code used for all errors which don't belong to a category
+const GENERIC_EXIT_CODE: i32 = 1;
+
+/// Describes the exit codes, displayed in the help
+pub const EXIT_CODES: &str = "Exit codes:
+  0  Success
+  1  Generic error
+  2  Invalid arguments
+  3  Authorization failed
+  4  No active playback device
+  5  Nothing playing
+  6  Spotify can't be reached";
+
+/// Error categories which are reported using distinct exit codes
+///
+/// Either returned directly or attached as context to errors, so main can find them in the chain
+#[derive(Debug)]
+pub enum Error {
+    Auth,
+    NoDevice,
+    NothingPlaying,
+    Offline,
+}
+
+impl Error {
+    /// Get the exit code of this error category
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Auth => 3,
+            Error::NoDevice => 4,
+            Error::NothingPlaying => 5,
+            Error::Offline => 6,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Auth => write!(
+                f,
+                "Authorization failed, try re-authorizing using --authorize"
+            ),
+            Error::NoDevice => write!(
+                f,
+                "No active Spotify devices found; open Spotify on a device and try again"
+            ),
+            Error::NothingPlaying => write!(f, "Nothing playing"),
+            Error::Offline => write!(f, "Can't reach Spotify, check your internet connection"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Get the exit code for an error, depending on the outermost error category attached to it
+pub fn exit_code(error: &anyhow::Error) -> i32 {
+    error
+        .downcast_ref::<Error>()
+        .map(Error::exit_code)
+        .unwrap_or(GENERIC_EXIT_CODE)
+}
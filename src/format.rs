@@ -0,0 +1,105 @@
+use std::time::Duration;
+
+use crate::model::Status;
+
+/// The icon displayed for the `{icon}` placeholder while playing
+const PLAYING_ICON: &str = "▶";
+
+/// The icon displayed for the `{icon}` placeholder while paused
+const PAUSED_ICON: &str = "⏸";
+
+/// The width of the `{bar}` placeholder in characters
+const PROGRESS_BAR_WIDTH: usize = 20;
+
+/// The placeholders which can be used in format strings
+pub const PLACEHOLDERS: &str =
+    "{icon}, {title}, {artist}, {state}, {progress}, {duration}, {remaining}, {bar}";
+
+/// Render a format string by replacing its placeholders with the playback status
+///
+/// Unknown placeholders are kept as they are
+pub fn render(format: &str, status: &Status) -> String {
+    let mut output = String::new();
+    let mut rest = format;
+
+    // Placeholders are replaced in a single pass, so values containing braces are left untouched
+    while let Some(start) = rest.find('{') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let end = match rest.find('}') {
+            Some(end) => end,
+            None => break,
+        };
+
+        match value(&rest[1..end], status) {
+            Some(value) => output.push_str(&value),
+            None => output.push_str(&rest[..=end]),
+        }
+
+        rest = &rest[end + 1..];
+    }
+
+    output.push_str(rest);
+    output
+}
+
+/// Get the value of a single placeholder
+fn value(placeholder: &str, status: &Status) -> Option<String> {
+    let value = match placeholder {
+        "icon" => match status.is_playing {
+            true => PLAYING_ICON.to_string(),
+            false => PAUSED_ICON.to_string(),
+        },
+        "title" => status.track.title.clone(),
+        "artist" => status.track.by.join(", "),
+        "state" => match status.is_playing {
+            true => "playing".to_string(),
+            false => "paused".to_string(),
+        },
+        "progress" => status.progress.map(minutes).unwrap_or_default(),
+        "duration" => status.duration.map(minutes).unwrap_or_default(),
+        "remaining" => remaining(status)
+            .map(|r| format!("-{}", minutes(r)))
+            .unwrap_or_default(),
+        "bar" => progress_bar(status),
+        _ => return None,
+    };
+
+    Some(value)
+}
+
+/// Get how much of the track is left to play
+fn remaining(status: &Status) -> Option<Duration> {
+    Some(status.duration?.saturating_sub(status.progress?))
+}
+
+/// Draw the playback progress of the track as a bar
+fn progress_bar(status: &Status) -> String {
+    let filled = match (status.progress, status.duration) {
+        (Some(progress), Some(duration)) if !duration.is_zero() => {
+            let ratio = progress.as_secs_f64() / duration.as_secs_f64();
+            ((ratio.clamp(0.0, 1.0) * PROGRESS_BAR_WIDTH as f64).round()) as usize
+        }
+        _ => 0,
+    };
+
+    "━".repeat(filled) + &"─".repeat(PROGRESS_BAR_WIDTH - filled)
+}
+
+/// Format a duration as minutes and seconds, e.g. "3:07"
+pub fn minutes(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Format a long duration as hours and minutes, e.g. "1 h 23 min"
+pub fn runtime(duration: Duration) -> String {
+    let minutes = duration.as_secs() / 60;
+
+    match minutes / 60 {
+        0 => format!("{} min", minutes),
+        hours => format!("{} h {} min", hours, minutes % 60),
+    }
+}
@@ -0,0 +1,57 @@
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+
+/// Items scoring at least this fraction of the best score match about as well as the best item
+const CLOSE_SCORE_RATIO: f64 = 0.9;
+
+/// The most items offered to choose from, when several items match about as well
+const MAX_CANDIDATES: usize = 5;
+
+/// The outcome of matching items against a name filter
+pub enum Match<T> {
+    /// A single item matches clearly better than all others
+    Best(T),
+    /// Several items match about as well, ordered from the best match
+    Candidates(Vec<T>),
+    None,
+}
+
+/// Match items by their names against a filter, tolerating typos like left out characters
+///
+/// An item whose name equals the filter, ignoring case, always matches best
+pub fn find<T>(items: Vec<T>, filter: &str, name: impl Fn(&T) -> String) -> Match<T> {
+    let matcher = SkimMatcherV2::default().ignore_case();
+
+    let mut scored: Vec<(i64, T)> = items
+        .into_iter()
+        .filter_map(|item| {
+            let name = name(&item);
+
+            match name.eq_ignore_ascii_case(filter) {
+                true => Some((i64::MAX, item)),
+                false => matcher
+                    .fuzzy_match(&name, filter)
+                    .map(|score| (score, item)),
+            }
+        })
+        .collect();
+
+    // The sort is stable, so equally scoring items keep their order
+    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+
+    let best = match scored.first() {
+        Some((score, _)) => *score as f64,
+        None => return Match::None,
+    };
+
+    let mut candidates: Vec<T> = scored
+        .into_iter()
+        .take_while(|(score, _)| *score as f64 >= best * CLOSE_SCORE_RATIO)
+        .take(MAX_CANDIDATES)
+        .map(|(_, item)| item)
+        .collect();
+
+    match candidates.len() {
+        1 => Match::Best(candidates.remove(0)),
+        _ => Match::Candidates(candidates),
+    }
+}
@@ -0,0 +1,197 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+use crate::{color, model::Status};
+
+/// Lyrics of a track, as returned by an lrclib compatible lyrics provider
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Lyrics {
+    plain_lyrics: Option<String>,
+    synced_lyrics: Option<String>,
+    #[serde(default)]
+    instrumental: bool,
+}
+
+/// A single line of synced lyrics
+struct SyncedLine {
+    time: Duration,
+    text: String,
+}
+
+/// Fetch the lyrics of the current track and print them
+///
+/// Synced lyrics highlight the line at the current playback progress, plain lyrics are printed if
+/// the provider has no synced lyrics for the track
+pub async fn print_lyrics(provider: &str, status: &Status, synced: bool) -> Result<()> {
+    let lyrics = match fetch(provider, status).await? {
+        Some(lyrics) => lyrics,
+        None => {
+            println!(
+                "No lyrics found for \"{}\" by {}",
+                status.track.title,
+                status.track.by.join(", ")
+            );
+            return Ok(());
+        }
+    };
+
+    if lyrics.instrumental {
+        println!("This track is instrumental");
+        return Ok(());
+    }
+
+    let synced_lines = lyrics.synced_lyrics.as_deref().map(parse_synced);
+
+    if synced {
+        match &synced_lines {
+            Some(lines) => {
+                print_synced(lines, status.progress.unwrap_or_default());
+                return Ok(());
+            }
+            None => eprintln!("No synced lyrics available, showing plain lyrics"),
+        }
+    }
+
+    // Fall back to the synced lyrics without their timestamps, if there are no plain lyrics
+    match (lyrics.plain_lyrics, synced_lines) {
+        (Some(plain), _) => println!("{}", plain.trim_end()),
+        (None, Some(lines)) => lines.iter().for_each(|l| println!("{}", l.text)),
+        (None, None) => println!("No lyrics found"),
+    }
+
+    Ok(())
+}
+
+/// Get the lyrics of a track from the lyrics provider
+///
+/// Returns Ok(None) if the provider doesn't know the track
+async fn fetch(provider: &str, status: &Status) -> Result<Option<Lyrics>> {
+    let mut query = vec![
+        ("track_name", status.track.title.clone()),
+        // Providers match the primary artist most reliably
+        (
+            "artist_name",
+            status.track.by.first().cloned().unwrap_or_default(),
+        ),
+    ];
+
+    if let Some(duration) = status.duration {
+        query.push(("duration", duration.as_secs().to_string()));
+    }
+
+    let response = reqwest::Client::new()
+        .get(provider)
+        .query(&query)
+        .header(
+            "User-Agent",
+            concat!(
+                "spotic/",
+                env!("CARGO_PKG_VERSION"),
+                " (",
+                env!("CARGO_PKG_HOMEPAGE"),
+                ")"
+            ),
+        )
+        .send()
+        .await
+        .context("Failed fetching lyrics")?;
+
+    if response.status() == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+
+    let lyrics = response
+        .error_for_status()
+        .context("Failed fetching lyrics")?
+        .json::<Lyrics>()
+        .await
+        .context("Failed parsing lyrics")?;
+
+    if !lyrics.instrumental && lyrics.plain_lyrics.is_none() && lyrics.synced_lyrics.is_none() {
+        return Ok(None);
+    }
+
+    Ok(Some(lyrics))
+}
+
+/// Parse synced lyrics in the LRC format, e.g. "[01:23.45] text"
+///
+/// Lines without a valid timestamp are skipped
+fn parse_synced(lyrics: &str) -> Vec<SyncedLine> {
+    lyrics
+        .lines()
+        .filter_map(|line| {
+            let (timestamp, text) = line.strip_prefix('[')?.split_once(']')?;
+            let (minutes, seconds) = timestamp.split_once(':')?;
+
+            let time = minutes.parse::<u64>().ok()? as f64 * 60.0 + seconds.parse::<f64>().ok()?;
+
+            Some(SyncedLine {
+                // Negative, infinite or NaN times come from malformed lines, which are skipped
+                time: Duration::try_from_secs_f64(time).ok()?,
+                text: text.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Print synced lyrics, highlighting the line at the playback progress
+fn print_synced(lines: &[SyncedLine], progress: Duration) {
+    let current = lines.iter().rposition(|l| l.time <= progress);
+
+    for (i, line) in lines.iter().enumerate() {
+        match Some(i) == current {
+            true => println!("> {}", color::name(&line.text)),
+            false => println!("  {}", line.text),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Get the time in milliseconds and text of every parsed line
+    fn parsed(lyrics: &str) -> Vec<(u128, String)> {
+        parse_synced(lyrics)
+            .into_iter()
+            .map(|line| (line.time.as_millis(), line.text))
+            .collect()
+    }
+
+    #[test]
+    fn parses_valid_lines() {
+        assert_eq!(
+            parsed("[00:01.50] Never gonna give you up\n[01:02.00]Never gonna let you down"),
+            vec![
+                (1500, "Never gonna give you up".to_string()),
+                (62000, "Never gonna let you down".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_empty_lines() {
+        assert_eq!(parsed("[00:10.00]"), vec![(10000, String::new())]);
+    }
+
+    #[test]
+    fn skips_metadata_lines() {
+        assert_eq!(
+            parsed("[ar:Rick Astley]\n[ti:Never Gonna Give You Up]\n[00:01.00] text"),
+            vec![(1000, "text".to_string())]
+        );
+    }
+
+    #[test]
+    fn skips_malformed_lines() {
+        let lyrics = "[00:-1.0] negative\n[00:NaN] nan\n[00:inf] infinite\n[-1:00.00] minutes\n\
+            [00:01.00 unclosed\nno timestamp\n[0001.00] no colon";
+
+        assert_eq!(parsed(lyrics), vec![]);
+    }
+}
@@ -1,18 +1,99 @@
-use anyhow::Result;
+use std::io::Write;
+
+use log::LevelFilter;
 
 mod args;
+mod art;
 mod auth;
+mod cache;
 mod client;
+mod color;
+mod config;
+mod error;
+mod format;
+mod fuzzy;
+mod lyrics;
 mod model;
+#[cfg(all(target_os = "linux", feature = "mpris"))]
+mod mpris;
+mod output;
+mod repl;
+mod tui;
 mod ui;
 
 //  TODO:
 //  Add configuration support for current song formatting, silent mode etc.
-//  Output current song ASCII cover art
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    args::parse().await?;
+fn main() {
+    let matches = args::command().get_matches();
+    init_logger(matches.get_count("verbose"));
+
+    // Config errors are reported once the config is loaded again while parsing the arguments,
+    // since the status bar output has to succeed regardless
+    let _ = config::apply_proxy();
+
+    let runtime = match tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("Error: Failed starting the async runtime: {:?}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // Errors are printed the same way returning them from main would, but with an exit code
+    // depending on their category
+    if let Err(e) = runtime.block_on(args::parse(matches)) {
+        eprintln!("Error: {:?}", e);
+        std::process::exit(error::exit_code(&e));
+    }
+}
+
+/// Log to stderr depending on how often the verbose flag was passed, nothing is logged without it
+///
+/// Only our own and rspotify's logs are shown, the HTTP libraries below are too noisy
+fn init_logger(verbosity: u8) {
+    let level = match verbosity {
+        0 => LevelFilter::Off,
+        1 => LevelFilter::Info,
+        _ => LevelFilter::Debug,
+    };
+
+    env_logger::Builder::new()
+        .filter_level(LevelFilter::Off)
+        .filter_module("sc", level)
+        .filter_module("rspotify", level)
+        .filter_module("rspotify_http", level)
+        .format(|buf, record| {
+            writeln!(
+                buf,
+                "[{} {}] {}",
+                record.level(),
+                record.target(),
+                redact_token(&record.args().to_string())
+            )
+        })
+        .init();
+}
+
+/// Hide access tokens, since rspotify logs the headers of every request it makes
+fn redact_token(message: &str) -> String {
+    let mut redacted = String::with_capacity(message.len());
+    let mut rest = message;
+
+    while let Some(start) = rest.find("Bearer ") {
+        let (before, after) = rest.split_at(start + "Bearer ".len());
+        redacted.push_str(before);
+        redacted.push_str("<redacted>");
+
+        let end = after
+            .find(|c: char| c == '"' || c.is_whitespace())
+            .unwrap_or(after.len());
+        rest = &after[end..];
+    }
 
-    Ok(())
+    redacted.push_str(rest);
+    redacted
 }
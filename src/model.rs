@@ -2,22 +2,153 @@ use std::{
     fmt::{Display, Formatter},
     future::Future,
     pin::Pin,
+    time::Duration,
 };
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use rspotify::{
     model::{
-        Device, FullArtist, FullTrack, PlayContextId, PlayableId, SimplifiedAlbum,
-        SimplifiedEpisode, SimplifiedPlaylist, SimplifiedShow,
+        AlbumId, ArtistId, Device, FullArtist, FullTrack, Image, Offset, PlayContextId, PlayableId,
+        PlayableItem, PlaylistId, PrivateUser, PublicUser, ShowId, SimplifiedAlbum,
+        SimplifiedEpisode, SimplifiedPlaylist, SimplifiedShow, SimplifiedTrack, SubscriptionLevel,
     },
-    prelude::OAuthClient,
+    prelude::{Id, OAuthClient},
     AuthCodePkceSpotify,
 };
 
+use serde::Serialize;
+
+use crate::{color, format};
+
 #[derive(Debug)]
 pub struct Track {
     pub title: String,
     pub by: Vec<String>,
+    pub id: Option<PlayableId<'static>>,
+    pub cover_images: Vec<Image>,
+}
+
+impl From<FullTrack> for Track {
+    fn from(track: FullTrack) -> Self {
+        Self {
+            title: track.name,
+            by: track.artists.into_iter().map(|a| a.name).collect(),
+            id: track.id.map(PlayableId::from),
+            cover_images: track.album.images,
+        }
+    }
+}
+
+impl From<PlayableItem> for Track {
+    fn from(item: PlayableItem) -> Self {
+        match item {
+            PlayableItem::Track(track) => Track::from(track),
+            PlayableItem::Episode(episode) => Self {
+                title: episode.name,
+                by: vec![episode.show.name],
+                id: Some(PlayableId::from(episode.id)),
+                cover_images: episode.images,
+            },
+        }
+    }
+}
+
+/// The current playback state
+#[derive(Debug)]
+pub struct Status {
+    pub track: Track,
+    pub is_playing: bool,
+    pub progress: Option<Duration>,
+    pub duration: Option<Duration>,
+}
+
+/// A serializable summary of the playback status, used for JSON output
+#[derive(Debug, Serialize)]
+pub struct StatusSummary {
+    pub title: String,
+    pub artists: Vec<String>,
+    pub uri: Option<String>,
+    pub is_playing: bool,
+    /// The playback progress in milliseconds
+    pub progress_ms: Option<u128>,
+    /// The duration of the track in milliseconds
+    pub duration_ms: Option<u128>,
+}
+
+impl From<&Status> for StatusSummary {
+    fn from(status: &Status) -> Self {
+        Self {
+            title: status.track.title.clone(),
+            artists: status.track.by.clone(),
+            uri: status.track.id.as_ref().map(|id| id.uri()),
+            is_playing: status.is_playing,
+            progress_ms: status.progress.map(|p| p.as_millis()),
+            duration_ms: status.duration.map(|d| d.as_millis()),
+        }
+    }
+}
+
+/// A playback status observed at a point in time, used for streaming JSON output
+#[derive(Debug, Serialize)]
+pub struct StatusEvent {
+    /// When the status was observed, in RFC 3339 format
+    pub timestamp: String,
+    /// The playback status, left out if nothing is playing
+    #[serde(flatten)]
+    pub status: Option<StatusSummary>,
+}
+
+/// Information about the authenticated user
+#[derive(Debug, Serialize)]
+pub struct UserInfo {
+    pub id: String,
+    pub display_name: Option<String>,
+    /// The subscription level, either "premium" or "free"
+    pub product: Option<String>,
+    /// The country as a two-letter country code
+    pub country: Option<String>,
+}
+
+impl From<PrivateUser> for UserInfo {
+    fn from(user: PrivateUser) -> Self {
+        Self {
+            id: user.id.id().to_string(),
+            display_name: user.display_name,
+            product: user.product.map(|p| match p {
+                SubscriptionLevel::Premium => "premium".to_string(),
+                SubscriptionLevel::Free => "free".to_string(),
+            }),
+            // Countries serialize to their two-letter code
+            country: user
+                .country
+                .and_then(|c| serde_json::to_value(c).ok())
+                .and_then(|c| c.as_str().map(|c| c.to_string())),
+        }
+    }
+}
+
+/// A serializable summary of a playable item, used for JSON output
+#[derive(Debug, Serialize)]
+pub struct PlayableSummary {
+    pub id: Option<String>,
+    /// The lowercase type, e.g. "track" or "playlist"
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub name: String,
+    pub artists: Vec<String>,
+    pub uri: Option<String>,
+}
+
+impl PlayableSummary {
+    fn new<I: Id>(id: Option<&I>, kind: &str, name: &str, artists: Vec<String>) -> Self {
+        Self {
+            id: id.map(|id| id.id().to_string()),
+            kind: kind.to_string(),
+            name: name.to_string(),
+            artists,
+            uri: id.map(|id| id.uri()),
+        }
+    }
 }
 
 pub struct DisplayableDevice {
@@ -35,19 +166,290 @@ pub trait Playable {
 
     fn type_string(&self) -> String;
 
+    /// Summarize this item for JSON output
+    fn to_summary(&self) -> PlayableSummary;
+
+    /// Play this item, starting at the track with the zero based offset if one is given
+    ///
+    /// Only playlists and albums can start at an offset, other items ignore it
     fn play<'a>(
         &'a self,
         client: &'a AuthCodePkceSpotify,
+        offset: Option<u32>,
     ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>>;
+
+    /// The ID used for adding this item to the queue, only tracks and episodes can be queued
+    fn playable_id(&self) -> Option<PlayableId<'static>> {
+        None
+    }
+
+    /// The album ID, if this item is an album whose tracks can be listed
+    fn album_id(&self) -> Option<AlbumId<'static>> {
+        None
+    }
+
+    /// The ID of the album this item is on, if it is a track whose album is known
+    fn track_album_id(&self) -> Option<AlbumId<'static>> {
+        None
+    }
+
+    /// The show ID, if this item is a show whose episodes can be listed
+    fn show_id(&self) -> Option<ShowId<'static>> {
+        None
+    }
+
+    /// The artist ID, if this item is an artist whose top tracks can be played
+    fn artist_id(&self) -> Option<ArtistId<'static>> {
+        None
+    }
+
+    /// The playlist ID, if this item is a playlist whose tracks can be listed
+    fn playlist_id(&self) -> Option<PlaylistId<'static>> {
+        None
+    }
+
+    /// The open.spotify.com URL of this item, if it has an ID
+    fn url(&self) -> Option<String> {
+        // URIs look like "spotify:track:<id>", URLs like "https://open.spotify.com/track/<id>"
+        let uri = self.to_summary().uri?;
+        let path = uri.strip_prefix("spotify:")?.replace(':', "/");
+
+        Some(format!("https://open.spotify.com/{}", path))
+    }
+
+    /// The owner of this item, if it is a playlist
+    fn owner(&self) -> Option<&PublicUser> {
+        None
+    }
+
+    /// Whether this item is marked as explicit, only tracks and episodes are marked
+    fn is_explicit(&self) -> bool {
+        false
+    }
+
+    /// Follow this item, only supported by some types
+    fn follow<'a>(
+        &'a self,
+        _client: &'a AuthCodePkceSpotify,
+    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
+        let error = anyhow!(
+            "Following is unsupported for this type ({})",
+            self.type_string()
+        );
+        Box::pin(async move { Err(error) })
+    }
+
+    /// Unfollow this item, only supported by some types
+    fn unfollow<'a>(
+        &'a self,
+        _client: &'a AuthCodePkceSpotify,
+    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
+        let error = anyhow!(
+            "Unfollowing is unsupported for this type ({})",
+            self.type_string()
+        );
+        Box::pin(async move { Err(error) })
+    }
+}
+
+/// Get the offset of the track with this zero based index in a context
+///
+/// rspotify sends the milliseconds of the duration as the track index
+fn track_offset(index: u32) -> Offset {
+    Offset::Position(chrono::Duration::milliseconds(index as i64))
 }
 
-impl Display for dyn Playable {
+impl Display for dyn Playable + '_ {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} [{}]", self.to_display(), self.type_string())
+        write!(
+            f,
+            "{} [{}]",
+            color::name(&self.to_display()),
+            color::label(&self.type_string())
+        )
+    }
+}
+
+/// An album or playlist displayed with its track count and total duration
+///
+/// Simplified albums and playlists don't include their tracks, so the details are fetched
+/// separately and only on request
+pub struct Detailed {
+    pub item: Box<dyn Playable>,
+    pub tracks: u32,
+    pub duration: Duration,
+}
+
+impl Playable for Detailed {
+    fn to_display(&self) -> String {
+        format!(
+            "{} — {} tracks, {}",
+            self.item.to_display(),
+            self.tracks,
+            format::runtime(self.duration)
+        )
+    }
+
+    fn type_string(&self) -> String {
+        self.item.type_string()
+    }
+
+    fn to_summary(&self) -> PlayableSummary {
+        self.item.to_summary()
+    }
+
+    fn play<'a>(
+        &'a self,
+        client: &'a AuthCodePkceSpotify,
+        offset: Option<u32>,
+    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
+        self.item.play(client, offset)
+    }
+
+    fn playable_id(&self) -> Option<PlayableId<'static>> {
+        self.item.playable_id()
+    }
+
+    fn album_id(&self) -> Option<AlbumId<'static>> {
+        self.item.album_id()
+    }
+
+    fn track_album_id(&self) -> Option<AlbumId<'static>> {
+        self.item.track_album_id()
+    }
+
+    fn show_id(&self) -> Option<ShowId<'static>> {
+        self.item.show_id()
+    }
+
+    fn artist_id(&self) -> Option<ArtistId<'static>> {
+        self.item.artist_id()
+    }
+
+    fn playlist_id(&self) -> Option<PlaylistId<'static>> {
+        self.item.playlist_id()
+    }
+
+    fn owner(&self) -> Option<&PublicUser> {
+        self.item.owner()
+    }
+
+    fn is_explicit(&self) -> bool {
+        self.item.is_explicit()
+    }
+
+    fn follow<'a>(
+        &'a self,
+        client: &'a AuthCodePkceSpotify,
+    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
+        self.item.follow(client)
+    }
+
+    fn unfollow<'a>(
+        &'a self,
+        client: &'a AuthCodePkceSpotify,
+    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
+        self.item.unfollow(client)
     }
 }
 
 impl Playable for FullTrack {
+    // Tracks with the same name are common, so the album and duration tell them apart
+    fn to_display(&self) -> String {
+        format!(
+            "\"{}\" by {} — {} ({})",
+            self.name,
+            self.artists
+                .iter()
+                .map(|a| a.name.clone())
+                .collect::<Vec<String>>()
+                .join(", "),
+            self.album.name,
+            format::minutes(self.duration.to_std().unwrap_or_default())
+        )
+    }
+
+    fn type_string(&self) -> String {
+        "Track".to_string()
+    }
+
+    fn to_summary(&self) -> PlayableSummary {
+        PlayableSummary::new(
+            self.id.as_ref(),
+            "track",
+            &self.name,
+            self.artists.iter().map(|a| a.name.clone()).collect(),
+        )
+    }
+
+    fn is_explicit(&self) -> bool {
+        self.explicit
+    }
+
+    fn playable_id(&self) -> Option<PlayableId<'static>> {
+        self.id.clone().map(PlayableId::from)
+    }
+
+    fn track_album_id(&self) -> Option<AlbumId<'static>> {
+        self.album.id.clone()
+    }
+
+    fn play<'a>(
+        &'a self,
+        client: &'a AuthCodePkceSpotify,
+        _offset: Option<u32>,
+    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
+        Box::pin(async move {
+            let id = self
+                .clone()
+                .id
+                .context("This song can't be played, since it lacks an ID. May be a local song.")?;
+            client
+                .start_uris_playback(vec![PlayableId::from(id)], None, None, None)
+                .await
+                .context("Failed to play track")?;
+            Ok(())
+        })
+    }
+}
+
+impl Playable for Track {
+    fn to_display(&self) -> String {
+        format!("\"{}\" by {}", self.title, self.by.join(", "))
+    }
+
+    fn type_string(&self) -> String {
+        "Track".to_string()
+    }
+
+    fn to_summary(&self) -> PlayableSummary {
+        PlayableSummary::new(self.id.as_ref(), "track", &self.title, self.by.clone())
+    }
+
+    fn playable_id(&self) -> Option<PlayableId<'static>> {
+        self.id.clone()
+    }
+
+    fn play<'a>(
+        &'a self,
+        client: &'a AuthCodePkceSpotify,
+        _offset: Option<u32>,
+    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
+        Box::pin(async move {
+            let id = self
+                .id
+                .clone()
+                .context("This song can't be played, since it lacks an ID. May be a local song.")?;
+            client
+                .start_uris_playback(vec![id], None, None, None)
+                .await
+                .context("Failed to play track")?;
+            Ok(())
+        })
+    }
+}
+
+impl Playable for SimplifiedTrack {
     fn to_display(&self) -> String {
         format!(
             "\"{}\" by {}",
@@ -64,9 +466,27 @@ impl Playable for FullTrack {
         "Track".to_string()
     }
 
+    fn to_summary(&self) -> PlayableSummary {
+        PlayableSummary::new(
+            self.id.as_ref(),
+            "track",
+            &self.name,
+            self.artists.iter().map(|a| a.name.clone()).collect(),
+        )
+    }
+
+    fn is_explicit(&self) -> bool {
+        self.explicit
+    }
+
+    fn playable_id(&self) -> Option<PlayableId<'static>> {
+        self.id.clone().map(PlayableId::from)
+    }
+
     fn play<'a>(
         &'a self,
         client: &'a AuthCodePkceSpotify,
+        _offset: Option<u32>,
     ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
         Box::pin(async move {
             let id = self
@@ -85,26 +505,70 @@ impl Playable for FullTrack {
 // Implement Playable for SimplifiedPlaylist
 impl Playable for SimplifiedPlaylist {
     fn to_display(&self) -> String {
-        format!("{}", self.name.clone())
+        self.name.clone()
     }
 
     fn type_string(&self) -> String {
         "Playlist".to_string()
     }
 
+    fn to_summary(&self) -> PlayableSummary {
+        PlayableSummary::new(Some(&self.id), "playlist", &self.name, Vec::new())
+    }
+
+    fn owner(&self) -> Option<&PublicUser> {
+        Some(&self.owner)
+    }
+
+    fn playlist_id(&self) -> Option<PlaylistId<'static>> {
+        Some(self.id.clone())
+    }
+
     fn play<'a>(
         &'a self,
         client: &'a AuthCodePkceSpotify,
+        offset: Option<u32>,
     ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
         Box::pin(async move {
             let id = self.clone().id;
             client
-                .start_context_playback(PlayContextId::Playlist(id), None, None, None)
+                .start_context_playback(
+                    PlayContextId::Playlist(id),
+                    None,
+                    offset.map(track_offset),
+                    None,
+                )
                 .await
                 .context("Failed to play playlist")?;
             Ok(())
         })
     }
+
+    fn follow<'a>(
+        &'a self,
+        client: &'a AuthCodePkceSpotify,
+    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
+        Box::pin(async move {
+            client
+                .playlist_follow(self.id.clone(), None)
+                .await
+                .context("Failed to follow playlist")?;
+            Ok(())
+        })
+    }
+
+    fn unfollow<'a>(
+        &'a self,
+        client: &'a AuthCodePkceSpotify,
+    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
+        Box::pin(async move {
+            client
+                .playlist_unfollow(self.id.clone())
+                .await
+                .context("Failed to unfollow playlist")?;
+            Ok(())
+        })
+    }
 }
 
 // Implement Playable for other types (albums, artists, etc.)
@@ -125,9 +589,23 @@ impl Playable for SimplifiedAlbum {
         "Album".to_string()
     }
 
+    fn to_summary(&self) -> PlayableSummary {
+        PlayableSummary::new(
+            self.id.as_ref(),
+            "album",
+            &self.name,
+            self.artists.iter().map(|a| a.name.clone()).collect(),
+        )
+    }
+
+    fn album_id(&self) -> Option<AlbumId<'static>> {
+        self.id.clone()
+    }
+
     fn play<'a>(
         &'a self,
         client: &'a AuthCodePkceSpotify,
+        offset: Option<u32>,
     ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
         Box::pin(async move {
             let id = self
@@ -135,7 +613,12 @@ impl Playable for SimplifiedAlbum {
                 .id
                 .context("This album can't be played, since it lacks an ID")?;
             client
-                .start_context_playback(PlayContextId::Album(id), None, None, None)
+                .start_context_playback(
+                    PlayContextId::Album(id),
+                    None,
+                    offset.map(track_offset),
+                    None,
+                )
                 .await
                 .context("Failed to play album")?;
             Ok(())
@@ -146,16 +629,25 @@ impl Playable for SimplifiedAlbum {
 // Implement Playable for FullArtist
 impl Playable for FullArtist {
     fn to_display(&self) -> String {
-        format!("{}", self.name)
+        self.name.clone()
     }
 
     fn type_string(&self) -> String {
         "Artist".to_string()
     }
 
+    fn to_summary(&self) -> PlayableSummary {
+        PlayableSummary::new(Some(&self.id), "artist", &self.name, Vec::new())
+    }
+
+    fn artist_id(&self) -> Option<ArtistId<'static>> {
+        Some(self.id.clone())
+    }
+
     fn play<'a>(
         &'a self,
         client: &'a AuthCodePkceSpotify,
+        _offset: Option<u32>,
     ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
         Box::pin(async move {
             let id = self.clone().id;
@@ -166,20 +658,55 @@ impl Playable for FullArtist {
             Ok(())
         })
     }
+
+    fn follow<'a>(
+        &'a self,
+        client: &'a AuthCodePkceSpotify,
+    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
+        Box::pin(async move {
+            client
+                .user_follow_artists([self.id.clone()])
+                .await
+                .context("Failed to follow artist")?;
+            Ok(())
+        })
+    }
+
+    fn unfollow<'a>(
+        &'a self,
+        client: &'a AuthCodePkceSpotify,
+    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
+        Box::pin(async move {
+            client
+                .user_unfollow_artists([self.id.clone()])
+                .await
+                .context("Failed to unfollow artist")?;
+            Ok(())
+        })
+    }
 }
 
 impl Playable for SimplifiedShow {
     fn to_display(&self) -> String {
-        format!("{}", self.name)
+        self.name.clone()
     }
 
     fn type_string(&self) -> String {
         "Show".to_string()
     }
 
+    fn to_summary(&self) -> PlayableSummary {
+        PlayableSummary::new(Some(&self.id), "show", &self.name, Vec::new())
+    }
+
+    fn show_id(&self) -> Option<ShowId<'static>> {
+        Some(self.id.clone())
+    }
+
     fn play<'a>(
         &'a self,
         client: &'a AuthCodePkceSpotify,
+        _offset: Option<u32>,
     ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
         Box::pin(async move {
             let id = self.clone().id;
@@ -194,21 +721,42 @@ impl Playable for SimplifiedShow {
 
 impl Playable for SimplifiedEpisode {
     fn to_display(&self) -> String {
-        format!("{}", self.name)
+        self.name.clone()
     }
 
     fn type_string(&self) -> String {
         "Episode".to_string()
     }
 
+    fn to_summary(&self) -> PlayableSummary {
+        PlayableSummary::new(Some(&self.id), "episode", &self.name, Vec::new())
+    }
+
+    fn is_explicit(&self) -> bool {
+        self.explicit
+    }
+
+    fn playable_id(&self) -> Option<PlayableId<'static>> {
+        Some(PlayableId::from(self.id.clone()))
+    }
+
     fn play<'a>(
         &'a self,
         client: &'a AuthCodePkceSpotify,
+        _offset: Option<u32>,
     ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
         Box::pin(async move {
             let id = self.clone().id;
+
+            // Resume where the user left off, unless the episode was already played completely
+            let position = self
+                .resume_point
+                .as_ref()
+                .filter(|r| !r.fully_played)
+                .map(|r| r.resume_position);
+
             client
-                .start_uris_playback(vec![PlayableId::from(id)], None, None, None)
+                .start_uris_playback(vec![PlayableId::from(id)], None, None, position)
                 .await
                 .context("Failed to play episode")?;
             Ok(())
@@ -0,0 +1,376 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use anyhow::{Context, Result};
+use rspotify::prelude::Id;
+use tokio::sync::Mutex;
+use zbus::{
+    fdo, interface,
+    zvariant::{ObjectPath, OwnedValue, Value},
+};
+
+use crate::{client::SpotifyPlayer, model::Status};
+
+/// The well known name the daemon registers on the session bus
+const BUS_NAME: &str = "org.mpris.MediaPlayer2.spotic";
+
+/// The object path MPRIS clients expect the interfaces at
+const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+
+/// The track ID used when nothing is playing, as defined by the MPRIS specification
+const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+
+/// The MPRIS root interface, describing the media player itself
+struct Root;
+
+#[interface(name = "org.mpris.MediaPlayer2")]
+impl Root {
+    fn raise(&self) {}
+
+    fn quit(&self) {}
+
+    #[zbus(property)]
+    fn can_quit(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_raise(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn has_track_list(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn identity(&self) -> String {
+        "spotic".to_string()
+    }
+
+    #[zbus(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    #[zbus(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// The MPRIS player interface, mapping playback controls to the spotify player
+struct Player {
+    player: Arc<Mutex<SpotifyPlayer>>,
+}
+
+impl Player {
+    /// Get the current playback status
+    async fn status(&self) -> fdo::Result<Option<Status>> {
+        self.player.lock().await.status().await.map_err(failed)
+    }
+}
+
+#[interface(name = "org.mpris.MediaPlayer2.Player")]
+impl Player {
+    async fn play_pause(&self) -> fdo::Result<()> {
+        self.player
+            .lock()
+            .await
+            .playback_toggle()
+            .await
+            .map_err(failed)
+    }
+
+    async fn play(&self) -> fdo::Result<()> {
+        self.player
+            .lock()
+            .await
+            .playback_resume()
+            .await
+            .map_err(failed)
+    }
+
+    async fn pause(&self) -> fdo::Result<()> {
+        self.player
+            .lock()
+            .await
+            .playback_pause()
+            .await
+            .map_err(failed)
+    }
+
+    // Spotify has no concept of stopping playback, so this pauses and seeks to the beginning
+    async fn stop(&self) -> fdo::Result<()> {
+        self.player
+            .lock()
+            .await
+            .stop()
+            .await
+            .map(|_| ())
+            .map_err(failed)
+    }
+
+    async fn next(&self) -> fdo::Result<()> {
+        self.player.lock().await.track_next().await.map_err(failed)
+    }
+
+    async fn previous(&self) -> fdo::Result<()> {
+        self.player
+            .lock()
+            .await
+            .track_prev(false)
+            .await
+            .map(|_| ())
+            .map_err(failed)
+    }
+
+    /// Seek by an offset in microseconds relative to the current position
+    async fn seek(&self, offset: i64) -> fdo::Result<()> {
+        let progress = self
+            .status()
+            .await?
+            .and_then(|s| s.progress)
+            .unwrap_or_default();
+
+        let position = (progress.as_micros() as i64).saturating_add(offset).max(0);
+
+        self.player
+            .lock()
+            .await
+            .seek(Duration::from_micros(position as u64))
+            .await
+            .map_err(failed)
+    }
+
+    /// Seek to a position in microseconds
+    async fn set_position(&self, _track_id: ObjectPath<'_>, position: i64) -> fdo::Result<()> {
+        // Negative positions must be ignored according to the specification
+        if position < 0 {
+            return Ok(());
+        }
+
+        self.player
+            .lock()
+            .await
+            .seek(Duration::from_micros(position as u64))
+            .await
+            .map_err(failed)
+    }
+
+    #[zbus(property)]
+    async fn playback_status(&self) -> fdo::Result<String> {
+        let status = match self.status().await? {
+            Some(s) if s.is_playing => "Playing",
+            Some(_) => "Paused",
+            None => "Stopped",
+        };
+
+        Ok(status.to_string())
+    }
+
+    #[zbus(property)]
+    async fn metadata(&self) -> fdo::Result<HashMap<String, OwnedValue>> {
+        let status = self.status().await?;
+
+        metadata(status.as_ref()).map_err(|e| fdo::Error::Failed(e.to_string()))
+    }
+
+    #[zbus(property)]
+    async fn position(&self) -> fdo::Result<i64> {
+        let progress = self.status().await?.and_then(|s| s.progress);
+
+        Ok(progress.map(|p| p.as_micros() as i64).unwrap_or(0))
+    }
+
+    #[zbus(property)]
+    async fn volume(&self) -> fdo::Result<f64> {
+        let volume = self
+            .player
+            .lock()
+            .await
+            .volume_get()
+            .await
+            .map_err(failed)?;
+
+        Ok(volume as f64 / 100.0)
+    }
+
+    #[zbus(property)]
+    async fn set_volume(&self, volume: f64) -> zbus::Result<()> {
+        let volume = (volume.clamp(0.0, 1.0) * 100.0).round() as u8;
+
+        self.player
+            .lock()
+            .await
+            .volume_set(volume)
+            .await
+            .map_err(|e| zbus::Error::from(failed(e)))
+    }
+
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn minimum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn maximum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn can_go_next(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_go_previous(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_play(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_pause(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_seek(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
+
+/// Expose the MPRIS interface on the session bus and serve requests until interrupted
+///
+/// Spotify is polled for playback changes whenever the poll interval elapses
+pub async fn run(player: SpotifyPlayer, poll_interval: Duration) -> Result<()> {
+    let player = Arc::new(Mutex::new(player));
+
+    let connection = zbus::connection::Builder::session()
+        .and_then(|b| b.name(BUS_NAME))
+        .and_then(|b| b.serve_at(OBJECT_PATH, Root))
+        .and_then(|b| {
+            b.serve_at(
+                OBJECT_PATH,
+                Player {
+                    player: player.clone(),
+                },
+            )
+        })
+        .context("Failed setting up the MPRIS interface")?
+        .build()
+        .await
+        .context("Failed connecting to the D-Bus session bus")?;
+
+    let player_ref = connection
+        .object_server()
+        .interface::<_, Player>(OBJECT_PATH)
+        .await
+        .context("Failed getting the MPRIS player interface")?;
+
+    println!(
+        "Exposing MPRIS interface as {}, press Ctrl-C to quit",
+        BUS_NAME
+    );
+
+    // Spotify doesn't notify us about playback changes, so poll for them and forward them to
+    // MPRIS clients ourselves
+    let mut last_state = None;
+
+    loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+            _ = tokio::time::sleep(poll_interval) => {}
+        }
+
+        let state = match player.lock().await.status().await {
+            Ok(status) => status.map(|s| (s.track.title, s.track.by, s.is_playing)),
+            Err(_) => continue,
+        };
+
+        if state == last_state {
+            continue;
+        }
+
+        last_state = state;
+
+        let iface = player_ref.get().await;
+        let emitter = player_ref.signal_emitter();
+
+        // Failing to notify clients is not fatal, they will get the new state on the next change
+        let _ = iface.metadata_changed(emitter).await;
+        let _ = iface.playback_status_changed(emitter).await;
+    }
+}
+
+/// Build the MPRIS metadata of the current track
+fn metadata(status: Option<&Status>) -> zbus::Result<HashMap<String, OwnedValue>> {
+    let mut metadata = HashMap::new();
+
+    let status = match status {
+        Some(status) => status,
+        None => {
+            metadata.insert(
+                "mpris:trackid".to_string(),
+                OwnedValue::from(ObjectPath::from_static_str_unchecked(NO_TRACK)),
+            );
+            return Ok(metadata);
+        }
+    };
+
+    let track = &status.track;
+
+    // Track IDs have to be valid object paths, spotify IDs only consist of alphanumerics
+    let track_id = match &track.id {
+        Some(id) => format!("/org/spotic/track/{}", id.id()),
+        None => NO_TRACK.to_string(),
+    };
+
+    metadata.insert(
+        "mpris:trackid".to_string(),
+        Value::from(ObjectPath::try_from(track_id)?).try_into()?,
+    );
+    metadata.insert(
+        "xesam:title".to_string(),
+        Value::from(track.title.clone()).try_into()?,
+    );
+    metadata.insert(
+        "xesam:artist".to_string(),
+        Value::from(track.by.clone()).try_into()?,
+    );
+
+    if let Some(duration) = status.duration {
+        metadata.insert(
+            "mpris:length".to_string(),
+            OwnedValue::from(duration.as_micros() as i64),
+        );
+    }
+
+    if let Some(image) = track.cover_images.first() {
+        metadata.insert(
+            "mpris:artUrl".to_string(),
+            Value::from(image.url.clone()).try_into()?,
+        );
+    }
+
+    Ok(metadata)
+}
+
+/// Map an error to a D-Bus error, so it is reported to the MPRIS client
+fn failed(error: anyhow::Error) -> fdo::Error {
+    fdo::Error::Failed(format!("{:#}", error))
+}
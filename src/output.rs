@@ -0,0 +1,23 @@
+use std::{fs, path::Path};
+
+use anyhow::{anyhow, Context, Result};
+
+/// Replace the contents of a file atomically, so readers never see a partially written file
+///
+/// The contents are written to a hidden temporary file in the same directory first, which is then
+/// renamed over the file
+pub fn write_atomic(path: &Path, contents: &str) -> Result<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("\"{}\" is not a file path", path.display()))?;
+
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(file_name);
+    temp_name.push(".tmp");
+    let temp_path = path.with_file_name(temp_name);
+
+    fs::write(&temp_path, contents)
+        .with_context(|| format!("Failed writing \"{}\"", temp_path.display()))?;
+
+    fs::rename(&temp_path, path).with_context(|| format!("Failed replacing \"{}\"", path.display()))
+}
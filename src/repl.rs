@@ -0,0 +1,236 @@
+use std::io::{BufRead, Write};
+
+use anyhow::{anyhow, Context, Result};
+use clap::{error::ErrorKind, ArgMatches};
+
+use crate::{args, client::SpotifyPlayer, config::Config};
+
+/// Read commands from stdin and run them using the same player, until the user quits
+///
+/// Keeping the player around avoids reloading the token cache for every command and keeps the
+/// playback device cache warm
+pub async fn run(player: &mut SpotifyPlayer, config: &Config) -> Result<()> {
+    println!(
+        "Enter commands without the leading \"sc\", \"help\" lists all commands and \"quit\" exits"
+    );
+
+    let stdin = std::io::stdin();
+
+    loop {
+        print!("sc> ");
+        std::io::stdout().flush().context("Failed writing prompt")?;
+
+        let mut line = String::new();
+
+        // Exit on end of input, e.g. when pressing Ctrl-D
+        if stdin.read_line(&mut line).context("Failed reading input")? == 0 {
+            println!();
+            return Ok(());
+        }
+
+        let words = match split_words(&line) {
+            Ok(words) => words,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                continue;
+            }
+        };
+
+        match words.first().map(|w| w.as_str()) {
+            None => continue,
+            Some("quit" | "exit") => return Ok(()),
+            _ => {}
+        }
+
+        if let Err(e) = run_words(player, words, config).await {
+            eprintln!("Error: {:#}", e);
+        }
+    }
+}
+
+/// Run one command per line read from stdin using the same player, e.g. from a script file
+///
+/// Empty lines and lines starting with "#" are skipped. Stops at the first failing command,
+/// unless errors should be ignored, in which case they are logged and counted
+pub async fn batch(
+    player: &mut SpotifyPlayer,
+    config: &Config,
+    continue_on_error: bool,
+) -> Result<()> {
+    let mut failed = 0;
+    let mut total = 0;
+
+    for (i, line) in std::io::stdin().lock().lines().enumerate() {
+        let line = line.context("Failed reading input")?;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        total += 1;
+
+        let result = match split_words(line) {
+            Ok(words) => run_words(player, words, config).await,
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = result {
+            let e = e.context(format!("Failed running line {} \"{}\"", i + 1, line));
+
+            if !continue_on_error {
+                return Err(e);
+            }
+
+            eprintln!("Error: {:#}", e);
+            failed += 1;
+        }
+    }
+
+    if failed > 0 {
+        return Err(anyhow!("{} of {} commands failed", failed, total));
+    }
+
+    Ok(())
+}
+
+/// Parse the words of a line as a command and run it
+async fn run_words(player: &mut SpotifyPlayer, words: Vec<String>, config: &Config) -> Result<()> {
+    let matches =
+        match args::command().try_get_matches_from(["sc".to_string()].into_iter().chain(words)) {
+            Ok(matches) => matches,
+            // Displaying help is not an error
+            Err(e) if matches!(e.kind(), ErrorKind::DisplayHelp | ErrorKind::DisplayVersion) => {
+                let _ = e.print();
+                return Ok(());
+            }
+            Err(e) => {
+                // Only keep the message itself, without the usage hints clap appends
+                let rendered = e.render().to_string();
+                let message = rendered.lines().next().unwrap_or_default();
+
+                return Err(anyhow!(message.trim_start_matches("error: ").to_string()));
+            }
+        };
+
+    run_line(player, &matches, config).await
+}
+
+/// Run a single command entered in the REPL or read in batch mode
+async fn run_line(player: &mut SpotifyPlayer, matches: &ArgMatches, config: &Config) -> Result<()> {
+    if matches.get_flag("authorize") {
+        return Err(anyhow!(
+            "Authorization can't be run from the REPL or in batch mode"
+        ));
+    }
+
+    if let Some(name @ ("repl" | "daemon" | "batch")) = matches.subcommand_name() {
+        return Err(anyhow!(
+            "The \"{}\" command can't be run inside the REPL or in batch mode",
+            name
+        ));
+    }
+
+    // Global flags are only applied once per session, so a line's own --dry-run is applied here
+    // and only lasts for this line
+    let session_dry_run = player.dry_run();
+    player.set_dry_run(session_dry_run || matches.get_flag("dry-run"));
+
+    let result = match args::switch_device(player, matches).await {
+        Ok(()) => args::dispatch(player, matches, config).await,
+        Err(e) => Err(e),
+    };
+
+    player.set_dry_run(session_dry_run);
+
+    result
+}
+
+/// Split a line into words like a shell would, words containing spaces can be quoted
+fn split_words(line: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+
+    for c in line.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => word.push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                in_word = true;
+            }
+            None if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            None => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+
+    if quote.is_some() {
+        return Err(anyhow!("Missing closing quote"));
+    }
+
+    if in_word {
+        words.push(word);
+    }
+
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(line: &str) -> Vec<String> {
+        split_words(line).unwrap()
+    }
+
+    #[test]
+    fn splits_on_whitespace() {
+        assert_eq!(words("volume  +5\n"), vec!["volume", "+5"]);
+    }
+
+    #[test]
+    fn keeps_quoted_words_together() {
+        assert_eq!(
+            words("play -t \"never gonna give you up\""),
+            vec!["play", "-t", "never gonna give you up"]
+        );
+        assert_eq!(words("play 'rick astley'"), vec!["play", "rick astley"]);
+    }
+
+    #[test]
+    fn keeps_other_quotes_inside_quotes() {
+        assert_eq!(words("play \"don't stop\""), vec!["play", "don't stop"]);
+    }
+
+    #[test]
+    fn joins_quotes_adjacent_to_words() {
+        assert_eq!(words("play ab\"c d\"e"), vec!["play", "abc de"]);
+    }
+
+    #[test]
+    fn keeps_empty_quoted_words() {
+        assert_eq!(words("play \"\""), vec!["play", ""]);
+    }
+
+    #[test]
+    fn splits_empty_lines_into_no_words() {
+        assert!(words("").is_empty());
+        assert!(words("   \t\n").is_empty());
+    }
+
+    #[test]
+    fn fails_on_unterminated_quotes() {
+        assert!(split_words("play \"never gonna").is_err());
+        assert!(split_words("play 'never gonna").is_err());
+    }
+}
@@ -0,0 +1,156 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout},
+    style::{Modifier, Style},
+    widgets::{Block, List, ListState, Paragraph},
+    DefaultTerminal, Frame,
+};
+
+use crate::{client::SpotifyPlayer, model::Playable};
+
+/// How long to wait after a playback command before refreshing, so spotify can catch up
+const COMMAND_REFRESH_DELAY: Duration = Duration::from_millis(500);
+
+/// How long to wait for key presses before checking if a refresh is due
+const POLL_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// The volume step used by the volume keybindings
+const VOLUME_STEP: u8 = 5;
+
+const KEYBINDINGS: &str =
+    " j/k: navigate | enter: play | space: toggle | n/p: next/prev | +/-: volume | q: quit ";
+
+/// State of the terminal UI
+struct App {
+    playlists: Vec<Box<dyn Playable>>,
+    list_state: ListState,
+    now_playing: String,
+    error: Option<String>,
+}
+
+/// Run the terminal UI until the user quits
+///
+/// The now playing footer is refreshed whenever the poll interval elapses
+pub async fn run(player: &mut SpotifyPlayer, poll_interval: Duration) -> Result<()> {
+    let playlists = player.playlists(None, None).await?;
+
+    // Select a playback device before taking over the terminal, since the device selection
+    // prompt can't be displayed inside the terminal UI
+    player.ensure_device().await?;
+
+    let now_playing = now_playing(player).await;
+
+    let mut list_state = ListState::default();
+    if !playlists.is_empty() {
+        list_state.select(Some(0));
+    }
+
+    let app = App {
+        playlists,
+        list_state,
+        now_playing,
+        error: None,
+    };
+
+    let mut terminal = ratatui::try_init().context("Failed initializing the terminal UI")?;
+
+    let result = event_loop(&mut terminal, player, app, poll_interval).await;
+
+    // Always restore the terminal, even if the event loop failed
+    ratatui::restore();
+
+    result
+}
+
+/// Handle key presses and refresh the now playing footer until the user quits
+async fn event_loop(
+    terminal: &mut DefaultTerminal,
+    player: &mut SpotifyPlayer,
+    mut app: App,
+    poll_interval: Duration,
+) -> Result<()> {
+    let mut next_refresh = Instant::now() + poll_interval;
+
+    loop {
+        terminal
+            .draw(|frame| draw(frame, &mut app))
+            .context("Failed drawing the terminal UI")?;
+
+        if event::poll(POLL_TIMEOUT).context("Failed reading terminal events")? {
+            if let Event::Key(key) = event::read().context("Failed reading terminal events")? {
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+
+                let result = match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        return Ok(())
+                    }
+                    KeyCode::Char('j') | KeyCode::Down => {
+                        app.list_state.select_next();
+                        continue;
+                    }
+                    KeyCode::Char('k') | KeyCode::Up => {
+                        app.list_state.select_previous();
+                        continue;
+                    }
+                    KeyCode::Enter => {
+                        match app.list_state.selected().and_then(|i| app.playlists.get(i)) {
+                            Some(playlist) => player.play(playlist.as_ref(), None).await,
+                            None => continue,
+                        }
+                    }
+                    KeyCode::Char(' ') => player.playback_toggle().await,
+                    KeyCode::Char('n') => player.track_next().await,
+                    KeyCode::Char('p') => player.track_prev(false).await.map(|_| ()),
+                    KeyCode::Char('+') => player.volume_up(VOLUME_STEP).await,
+                    KeyCode::Char('-') => player.volume_down(VOLUME_STEP).await,
+                    _ => continue,
+                };
+
+                app.error = result.err().map(|e| e.to_string());
+                next_refresh = Instant::now() + COMMAND_REFRESH_DELAY;
+            }
+        }
+
+        if Instant::now() >= next_refresh {
+            app.now_playing = now_playing(player).await;
+            next_refresh = Instant::now() + poll_interval;
+        }
+    }
+}
+
+/// Draw the playlist pane and the now playing footer
+fn draw(frame: &mut Frame, app: &mut App) {
+    let [list_area, footer_area] =
+        Layout::vertical([Constraint::Min(1), Constraint::Length(3)]).areas(frame.area());
+
+    let list = List::new(app.playlists.iter().map(|p| p.to_display()))
+        .block(Block::bordered().title(" Playlists "))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .highlight_symbol("> ");
+
+    frame.render_stateful_widget(list, list_area, &mut app.list_state);
+
+    // Show the last error in place of the current track until a command succeeds
+    let footer = Paragraph::new(app.error.clone().unwrap_or(app.now_playing.clone())).block(
+        Block::bordered()
+            .title(" Now playing ")
+            .title_bottom(KEYBINDINGS),
+    );
+
+    frame.render_widget(footer, footer_area);
+}
+
+/// Get the text displayed in the now playing footer
+async fn now_playing(player: &mut SpotifyPlayer) -> String {
+    match player.current_track().await {
+        Ok(Some(t)) => format!("\"{}\" by {}", t.title, t.by.join(", ")),
+        Ok(None) => "Nothing playing".to_string(),
+        Err(e) => e.to_string(),
+    }
+}
@@ -1,5 +1,7 @@
+use std::fmt::{Display, Formatter};
+
 use anyhow::{anyhow, Context, Result};
-use inquire::{Password, PasswordDisplayMode, Select, Text};
+use inquire::{Confirm, MultiSelect, Password, PasswordDisplayMode, Select, Text};
 use rspotify::{model::Device, Credentials};
 
 use crate::model::{DisplayableDevice, Playable};
@@ -11,6 +13,151 @@ pub fn select_playable(playables: Vec<Box<dyn Playable>>) -> Result<Box<dyn Play
         .context("Failed to select a playable item")
 }
 
+/// Select several playable items from a list and return them in the listed order
+pub fn multiselect_playable(playables: Vec<Box<dyn Playable>>) -> Result<Vec<Box<dyn Playable>>> {
+    MultiSelect::new("Select items to queue", playables)
+        .prompt()
+        .context("Failed to select playable items")
+}
+
+/// The outcome of a selection prompt which can request more items
+pub enum PagedSelection {
+    /// The index of the selected item
+    Selected(usize),
+    /// The user requested more items to be loaded
+    LoadMore,
+}
+
+/// An entry of a selection prompt which can request more items
+enum PagedOption<'a> {
+    Item(&'a dyn Playable),
+    LoadMore,
+}
+
+impl Display for PagedOption<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PagedOption::Item(item) => write!(f, "{}", item),
+            PagedOption::LoadMore => write!(f, "Load more…"),
+        }
+    }
+}
+
+/// Select a playable item from a list, optionally offering to load more items
+///
+/// The cursor starts at the given index, so after loading more items the user continues where
+/// the new items begin
+pub fn select_playable_paged(
+    playables: &[Box<dyn Playable>],
+    load_more: bool,
+    cursor: usize,
+) -> Result<PagedSelection> {
+    let mut options: Vec<PagedOption> = playables
+        .iter()
+        .map(|p| PagedOption::Item(p.as_ref()))
+        .collect();
+
+    if load_more {
+        options.push(PagedOption::LoadMore);
+    }
+
+    let selected = Select::new("Select an item to play", options)
+        .with_starting_cursor(cursor)
+        .raw_prompt()
+        .context("Failed to select a playable item")?;
+
+    // Items are listed in the same order as passed in, only the sentinel is appended
+    Ok(match selected.value {
+        PagedOption::Item(_) => PagedSelection::Selected(selected.index),
+        PagedOption::LoadMore => PagedSelection::LoadMore,
+    })
+}
+
+/// What to do with a selected album
+pub enum AlbumAction {
+    Play,
+    ShowTracks,
+}
+
+impl Display for AlbumAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AlbumAction::Play => write!(f, "Play album"),
+            AlbumAction::ShowTracks => write!(f, "Show tracks"),
+        }
+    }
+}
+
+/// Select whether to play a selected album or to show its tracks
+pub fn select_album_action() -> Result<AlbumAction> {
+    Select::new(
+        "What do you want to do with this album?",
+        vec![AlbumAction::Play, AlbumAction::ShowTracks],
+    )
+    .prompt()
+    .context("Failed selecting an album action")
+}
+
+/// What to do with a selected show
+pub enum ShowAction {
+    Play,
+    ShowEpisodes,
+}
+
+impl Display for ShowAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShowAction::Play => write!(f, "Play show"),
+            ShowAction::ShowEpisodes => write!(f, "List episodes"),
+        }
+    }
+}
+
+/// Select whether to play a selected show or to list its episodes
+pub fn select_show_action() -> Result<ShowAction> {
+    Select::new(
+        "What do you want to do with this show?",
+        vec![ShowAction::Play, ShowAction::ShowEpisodes],
+    )
+    .prompt()
+    .context("Failed selecting a show action")
+}
+
+/// What to do with a selected track or episode
+pub enum ItemAction {
+    Play,
+    Queue,
+    Cancel,
+}
+
+impl Display for ItemAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ItemAction::Play => write!(f, "Play now"),
+            ItemAction::Queue => write!(f, "Add to queue"),
+            ItemAction::Cancel => write!(f, "Cancel"),
+        }
+    }
+}
+
+/// Select whether to play a selected item now or to add it to the queue
+pub fn select_item_action() -> Result<ItemAction> {
+    Select::new(
+        "What do you want to do with this item?",
+        vec![ItemAction::Play, ItemAction::Queue, ItemAction::Cancel],
+    )
+    .prompt()
+    .context("Failed selecting an action")
+}
+
+/// Ask the user to confirm an action, defaulting to no
+pub fn confirm(prompt: &str) -> Result<bool> {
+    Confirm::new(prompt)
+        .with_default(false)
+        .prompt()
+        .context("Failed reading confirmation")
+}
+
 /// Display a selection prompt for playback devices
 pub fn select_device(devices: Vec<Device>) -> Result<Device> {
     let devices: Vec<DisplayableDevice> = devices
@@ -18,7 +165,7 @@ pub fn select_device(devices: Vec<Device>) -> Result<Device> {
         .map(|device| DisplayableDevice { device })
         .collect();
 
-    if devices.len() == 0 {
+    if devices.is_empty() {
         return Err(anyhow!("No playback devices available")
             .context("Please make sure you are running a Spotify client"));
     }
@@ -31,7 +178,7 @@ pub fn select_device(devices: Vec<Device>) -> Result<Device> {
 }
 
 /// Collect client id and client secrets
-pub fn collect_creds(callback_uri: &'static str) -> Result<Credentials> {
+pub fn collect_creds(callback_uri: &str) -> Result<Credentials> {
     println!(
 "To authorize this tool you need to provide client credentials.
 
@@ -45,6 +192,8 @@ To get these credentials go to the Spotify Developer Dashboard: https://develope
 4. Accept the Terms of Service and finally click \"Save\".
 5. Now click on the newly created app and go to the settings.
 6. Here you will find the client id and the client secret.
+
+If you change the callback port later on, the Redirect URI in the dashboard has to be updated to match.
 ", callback_uri
     );
 